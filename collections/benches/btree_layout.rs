@@ -0,0 +1,79 @@
+//! Compares the flat [Map], the paged [PagedMap] and [BTreeMap]
+//!
+//! Run with `cargo bench -p collections --bench btree_layout`,
+//! pass the sizes as arguments to override the default 1k/100k/10M
+
+use collections::btree::{Map, PagedMap};
+use random::Source;
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Flat inserts shift the whole tail of the Vec and are skipped beyond this size
+const FLAT_INSERT_LIMIT: usize = 200_000;
+const LOOKUPS: usize = 1_000_000;
+
+fn keys(n: usize, seed: u64) -> Vec<u64> {
+    let mut source = random::default(seed);
+    (0..n).map(|_| source.read_u64()).collect()
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let out = f();
+    (out, start.elapsed())
+}
+
+fn report(size: usize, layout: &str, op: &str, ops: usize, d: Duration) {
+    let ns = d.as_nanos() as f64 / ops.max(1) as f64;
+    println!("{:>10} {:<8} {:<8} {:>12.1?} {:>10.1} ns/op", size, layout, op, d, ns);
+}
+
+fn bench(size: usize) {
+    let input = keys(size, 0x9e37_79b9_7f4a_7c15);
+    let lookups = keys(LOOKUPS.min(size * 10), 0x2545_f491_4f6c_dd1d);
+    let lookups: Vec<_> = lookups.iter().map(|i| input[*i as usize % size]).collect();
+
+    let (flat, d) = time(|| input.iter().map(|k| (*k, *k)).collect::<Map<_, _>>());
+    report(size, "flat", "collect", size, d);
+    if size <= FLAT_INSERT_LIMIT {
+        let (_, d) = time(|| {
+            let mut map = Map::new();
+            input.iter().for_each(|k| { map.insert(*k, *k); });
+            map
+        });
+        report(size, "flat", "insert", size, d);
+    }
+    let (_, d) = time(|| lookups.iter().filter(|k| flat.contains(*k)).count());
+    report(size, "flat", "get", lookups.len(), d);
+    let (_, d) = time(|| flat.iter().map(|(_, v)| black_box(*v)).sum::<u64>());
+    report(size, "flat", "iter", size, d);
+
+    let (paged, d) = time(|| {
+        let mut map = PagedMap::<_, _>::default();
+        input.iter().for_each(|k| { map.insert(*k, *k); });
+        map
+    });
+    report(size, "paged", "insert", size, d);
+    let (_, d) = time(|| lookups.iter().filter(|k| paged.contains(*k)).count());
+    report(size, "paged", "get", lookups.len(), d);
+    let (_, d) = time(|| paged.iter().map(|(_, v)| black_box(*v)).sum::<u64>());
+    report(size, "paged", "iter", size, d);
+
+    let (std, d) = time(|| {
+        let mut map = BTreeMap::new();
+        input.iter().for_each(|k| { map.insert(*k, *k); });
+        map
+    });
+    report(size, "std", "insert", size, d);
+    let (_, d) = time(|| lookups.iter().filter(|k| std.contains_key(*k)).count());
+    report(size, "std", "get", lookups.len(), d);
+    let (_, d) = time(|| std.values().map(|v| black_box(*v)).sum::<u64>());
+    report(size, "std", "iter", size, d);
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1).filter_map(|a| a.parse().ok()).collect();
+    let sizes = if sizes.is_empty() { vec![1_000, 100_000, 10_000_000] } else { sizes };
+    sizes.into_iter().for_each(bench);
+}
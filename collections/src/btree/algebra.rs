@@ -0,0 +1,232 @@
+use super::{Compare, Layout, Map, Set, SetIter};
+use core::cmp::Ordering;
+use core::iter::Peekable;
+use core::marker::PhantomData;
+use core::ops::{BitAnd, BitOr, BitXor, Sub};
+
+/// Walks two sorted sets in lockstep, yielding the keys of one step
+/// from the left set, the right set or both of them when they are equal
+struct Merge<'s, K, C> {
+    a: Peekable<SetIter<'s, K>>,
+    b: Peekable<SetIter<'s, K>>,
+    order: PhantomData<C>,
+}
+
+impl<'s, K, C: Compare<K>> Merge<'s, K, C> {
+    fn new<L>(a: &'s Set<K, C, L>, b: &'s Set<K, C, L>) -> Self {
+        Self { a: a.iter().peekable(), b: b.iter().peekable(), order: PhantomData }
+    }
+
+    fn nexts(&mut self) -> (Option<&'s K>, Option<&'s K>) {
+        let ord = match (self.a.peek(), self.b.peek()) {
+            (None, _) => Ordering::Greater,
+            (_, None) => Ordering::Less,
+            (Some(a), Some(b)) => C::compare(a, b),
+        };
+        match ord {
+            Ordering::Less => (self.a.next(), None),
+            Ordering::Greater => (None, self.b.next()),
+            Ordering::Equal => (self.a.next(), self.b.next()),
+        }
+    }
+}
+
+pub struct Union<'s, K, C = super::Natural>(Merge<'s, K, C>);
+
+impl<'s, K, C: Compare<K>> Iterator for Union<'s, K, C> {
+    type Item = &'s K;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (a, b) = self.0.nexts();
+        a.or(b)
+    }
+}
+
+pub struct Intersection<'s, K, C = super::Natural>(Merge<'s, K, C>);
+
+impl<'s, K, C: Compare<K>> Iterator for Intersection<'s, K, C> {
+    type Item = &'s K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let (Some(a), Some(_)) = self.0.nexts() {
+                return Some(a);
+            }
+            if self.0.a.peek().is_none() || self.0.b.peek().is_none() {
+                return None;
+            }
+        }
+    }
+}
+
+pub struct Difference<'s, K, C = super::Natural>(Merge<'s, K, C>);
+
+impl<'s, K, C: Compare<K>> Iterator for Difference<'s, K, C> {
+    type Item = &'s K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.nexts() {
+                (Some(a), None) => return Some(a),
+                (Some(_), Some(_)) => {}
+                (None, _) => {
+                    self.0.a.peek()?;
+                }
+            }
+        }
+    }
+}
+
+pub struct SymmetricDifference<'s, K, C = super::Natural>(Merge<'s, K, C>);
+
+impl<'s, K, C: Compare<K>> Iterator for SymmetricDifference<'s, K, C> {
+    type Item = &'s K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.nexts() {
+                (Some(_), Some(_)) => {}
+                (a, b) => return a.or(b),
+            }
+        }
+    }
+}
+
+impl<K, C: Compare<K>, L> Set<K, C, L> {
+    /// Visits the keys present in `self` or `other` in ascending order
+    #[inline]
+    pub fn union<'s>(&'s self, other: &'s Set<K, C, L>) -> Union<'s, K, C> {
+        Union(Merge::new(self, other))
+    }
+
+    /// Visits the keys present in both `self` and `other` in ascending order
+    #[inline]
+    pub fn intersection<'s>(&'s self, other: &'s Set<K, C, L>) -> Intersection<'s, K, C> {
+        Intersection(Merge::new(self, other))
+    }
+
+    /// Visits the keys present in `self` but not in `other` in ascending order
+    #[inline]
+    pub fn difference<'s>(&'s self, other: &'s Set<K, C, L>) -> Difference<'s, K, C> {
+        Difference(Merge::new(self, other))
+    }
+
+    /// Visits the keys present in exactly one of the sets in ascending order
+    #[inline]
+    pub fn symmetric_difference<'s>(
+        &'s self,
+        other: &'s Set<K, C, L>,
+    ) -> SymmetricDifference<'s, K, C> {
+        SymmetricDifference(Merge::new(self, other))
+    }
+
+    pub fn is_subset(&self, other: &Set<K, C, L>) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    #[inline]
+    pub fn is_superset(&self, other: &Set<K, C, L>) -> bool {
+        other.is_subset(self)
+    }
+
+    #[inline]
+    pub fn is_disjoint(&self, other: &Set<K, C, L>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
+
+impl<K, C, L: Layout> Set<K, C, L> {
+    /// Keys must be sorted and unique
+    fn from_sorted<I: Iterator<Item = K>>(keys: I) -> Self {
+        let records = keys.map(|k| (k, ()).into()).collect();
+        Self(Map::from_sorted(records))
+    }
+}
+
+impl<K: Clone, C: Compare<K>, L: Layout> BitOr<&Set<K, C, L>> for &Set<K, C, L> {
+    type Output = Set<K, C, L>;
+
+    #[inline]
+    fn bitor(self, rhs: &Set<K, C, L>) -> Set<K, C, L> {
+        Set::from_sorted(self.union(rhs).cloned())
+    }
+}
+
+impl<K: Clone, C: Compare<K>, L: Layout> BitAnd<&Set<K, C, L>> for &Set<K, C, L> {
+    type Output = Set<K, C, L>;
+
+    #[inline]
+    fn bitand(self, rhs: &Set<K, C, L>) -> Set<K, C, L> {
+        Set::from_sorted(self.intersection(rhs).cloned())
+    }
+}
+
+impl<K: Clone, C: Compare<K>, L: Layout> Sub<&Set<K, C, L>> for &Set<K, C, L> {
+    type Output = Set<K, C, L>;
+
+    #[inline]
+    fn sub(self, rhs: &Set<K, C, L>) -> Set<K, C, L> {
+        Set::from_sorted(self.difference(rhs).cloned())
+    }
+}
+
+impl<K: Clone, C: Compare<K>, L: Layout> BitXor<&Set<K, C, L>> for &Set<K, C, L> {
+    type Output = Set<K, C, L>;
+
+    #[inline]
+    fn bitxor(self, rhs: &Set<K, C, L>) -> Set<K, C, L> {
+        Set::from_sorted(self.symmetric_difference(rhs).cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set(keys: &[u32]) -> Set<u32> {
+        keys.iter().cloned().collect()
+    }
+
+    fn keys<'s, I: Iterator<Item = &'s u32>>(iter: I) -> Vec<u32> {
+        iter.cloned().collect()
+    }
+
+    #[test]
+    fn lazy_algebra() {
+        let a = set(&[1, 2, 4, 6, 8]);
+        let b = set(&[2, 3, 4, 9]);
+        assert_eq!(keys(a.union(&b)), vec![1, 2, 3, 4, 6, 8, 9]);
+        assert_eq!(keys(a.intersection(&b)), vec![2, 4]);
+        assert_eq!(keys(a.difference(&b)), vec![1, 6, 8]);
+        assert_eq!(keys(b.difference(&a)), vec![3, 9]);
+        assert_eq!(keys(a.symmetric_difference(&b)), vec![1, 3, 6, 8, 9]);
+        let empty = set(&[]);
+        assert_eq!(keys(a.intersection(&empty)), vec![]);
+        assert_eq!(keys(empty.difference(&a)), vec![]);
+        assert_eq!(keys(a.difference(&empty)), vec![1, 2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn relations() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[1, 3]);
+        let c = set(&[4, 5]);
+        assert!(b.is_subset(&a));
+        assert!(!a.is_subset(&b));
+        assert!(a.is_superset(&b));
+        assert!(a.is_disjoint(&c));
+        assert!(!a.is_disjoint(&b));
+        assert!(set(&[]).is_subset(&c));
+    }
+
+    #[test]
+    fn operators() {
+        let a = set(&[1, 2, 3]);
+        let b = set(&[3, 4]);
+        assert_eq!((&a | &b).into_vec(), vec![1, 2, 3, 4]);
+        assert_eq!((&a & &b).into_vec(), vec![3]);
+        assert_eq!((&a - &b).into_vec(), vec![1, 2]);
+        assert_eq!((&a ^ &b).into_vec(), vec![1, 2, 4]);
+    }
+}
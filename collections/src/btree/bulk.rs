@@ -0,0 +1,187 @@
+use super::{Compare, Layout, Map, Set};
+use super::layout::Pages;
+use crate::record::Rec;
+use core::cmp::Ordering;
+use core::iter::FromIterator;
+use core::mem;
+
+/// Which value survives when bulk input contains the same key more than once
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dedup {
+    KeepFirst,
+    KeepLast,
+}
+
+impl Default for Dedup {
+    /// Matches [Map::insert], where a later value replaces an earlier one
+    #[inline]
+    fn default() -> Self {
+        Dedup::KeepLast
+    }
+}
+
+impl<K, V, C: Compare<K>, L: Layout> Map<K, V, C, L> {
+    /// Builds the map from unsorted pairs with a single sort pass
+    pub fn from_iter_with<I>(iter: I, dedup: Dedup) -> Self
+    where I: IntoIterator<Item = (K, V)>
+    {
+        Self::from_sorted(sorted_records::<K, V, C, I>(iter, dedup))
+    }
+
+    /// Inserts unsorted pairs with a single sort pass and a linear merge
+    ///
+    /// With [Dedup::KeepFirst] values already stored in the map are preserved
+    pub fn extend_with<I>(&mut self, iter: I, dedup: Dedup)
+    where I: IntoIterator<Item = (K, V)>
+    {
+        let records = sorted_records::<K, V, C, I>(iter, dedup);
+        self.merge(records, dedup)
+    }
+
+    /// Moves all records from `other` into `self`, leaving `other` empty
+    ///
+    /// Both maps are already sorted, so they are merged in linear time.
+    /// Values from `other` replace values of equal keys in `self`
+    pub fn append(&mut self, other: &mut Self) {
+        let records = mem::take(&mut other.records).into_records();
+        self.merge(records, Dedup::KeepLast)
+    }
+
+    fn merge(&mut self, records: Vec<Rec<K, V>>, dedup: Dedup) {
+        match (self.records.last(), records.first()) {
+            (_, None) => {}
+            (None, _) => self.records = Pages::from_records(records, L::PAGE_SIZE),
+            (Some(l), Some(r)) if C::compare(&l.key, &r.key) == Ordering::Less => {
+                self.records.append(records, L::PAGE_SIZE)
+            }
+            _ => self.merge_overlapping(records, dedup),
+        }
+    }
+
+    fn merge_overlapping(&mut self, records: Vec<Rec<K, V>>, dedup: Dedup) {
+        let left = mem::take(&mut self.records).into_records();
+        let mut out = Vec::with_capacity(left.len() + records.len());
+        let mut l = left.into_iter().peekable();
+        let mut r = records.into_iter().peekable();
+        while let (Some(a), Some(b)) = (l.peek(), r.peek()) {
+            match C::compare(&a.key, &b.key) {
+                Ordering::Less => out.extend(l.next()),
+                Ordering::Greater => out.extend(r.next()),
+                Ordering::Equal => {
+                    let (a, b) = (l.next(), r.next());
+                    out.extend(match dedup {
+                        Dedup::KeepFirst => a,
+                        Dedup::KeepLast => a.zip(b).map(|(a, b)| (a.key, b.val).into()),
+                    });
+                }
+            }
+        }
+        out.extend(l);
+        out.extend(r);
+        self.records = Pages::from_records(out, L::PAGE_SIZE);
+    }
+}
+
+fn sorted_records<K, V, C: Compare<K>, I>(iter: I, dedup: Dedup) -> Vec<Rec<K, V>>
+where I: IntoIterator<Item = (K, V)>
+{
+    let mut records: Vec<Rec<K, V>> = iter.into_iter().map(Rec::from).collect();
+    // stable sort keeps equal keys in input order
+    records.sort_by(|a, b| C::compare(&a.key, &b.key));
+    match dedup {
+        Dedup::KeepFirst => records.dedup_by(|a, b| C::compare(&a.key, &b.key).is_eq()),
+        Dedup::KeepLast => records.dedup_by(|a, b| {
+            let same = C::compare(&a.key, &b.key).is_eq();
+            if same {
+                mem::swap(&mut a.val, &mut b.val);
+            }
+            same
+        }),
+    }
+    records
+}
+
+impl<K, V, C: Compare<K>, L: Layout> FromIterator<(K, V)> for Map<K, V, C, L> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_with(iter, Dedup::default())
+    }
+}
+
+impl<K, V, C: Compare<K>, L: Layout> Extend<(K, V)> for Map<K, V, C, L> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.extend_with(iter, Dedup::default())
+    }
+}
+
+impl<K, C: Compare<K>, L: Layout> Set<K, C, L> {
+    /// Moves all keys from `other` into `self`, leaving `other` empty
+    #[inline]
+    pub fn append(&mut self, other: &mut Self) {
+        self.0.append(&mut other.0)
+    }
+}
+
+impl<K, C: Compare<K>, L: Layout> FromIterator<K> for Set<K, C, L> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        let keys = iter.into_iter().map(|k| (k, ()));
+        Self(Map::from_iter_with(keys, Dedup::KeepFirst))
+    }
+}
+
+impl<K, C: Compare<K>, L: Layout> Extend<K> for Set<K, C, L> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
+        let keys = iter.into_iter().map(|k| (k, ()));
+        self.0.extend_with(keys, Dedup::KeepFirst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collect_with_dedup() {
+        let pairs = vec![(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e')];
+        let last: Map<_, _> = pairs.iter().cloned().collect();
+        assert_eq!(last.into_vec(), vec![(1, 'e'), (2, 'd'), (3, 'c')]);
+        let first: Map<_, _> = Map::from_iter_with(pairs, Dedup::KeepFirst);
+        assert_eq!(first.into_vec(), vec![(1, 'b'), (2, 'd'), (3, 'a')]);
+    }
+
+    #[test]
+    fn extend_merges_existing() {
+        let mut map: Map<_, _> = vec![(1, 'a'), (4, 'b'), (6, 'c')].into_iter().collect();
+        map.extend(vec![(5, 'x'), (4, 'y'), (0, 'z')]);
+        assert_eq!(map.get(&4), Some(&'y'));
+        map.extend_with(vec![(6, 'w'), (7, 'v')], Dedup::KeepFirst);
+        assert_eq!(
+            map.into_vec(),
+            vec![(0, 'z'), (1, 'a'), (4, 'y'), (5, 'x'), (6, 'c'), (7, 'v')]
+        );
+    }
+
+    #[test]
+    fn append_sorted_maps() {
+        let mut a: Map<_, _> = (0..10).step_by(2).map(|i| (i, i)).collect();
+        let mut b: Map<_, _> = (0..10).step_by(3).map(|i| (i, i * 10)).collect();
+        a.append(&mut b);
+        assert!(b.is_empty());
+        let keys: Vec<_> = a.keys().cloned().collect();
+        assert_eq!(keys, vec![0, 2, 3, 4, 6, 8, 9]);
+        assert_eq!(a.get(&6), Some(&60));
+        let mut tail: Map<_, _> = vec![(20, 0)].into_iter().collect();
+        a.append(&mut tail);
+        assert_eq!(a.last(), Some((&20, &0)));
+    }
+
+    #[test]
+    fn collect_set() {
+        let mut set: Set<_> = vec![5, 1, 5, 3].into_iter().collect();
+        set.extend(vec![2, 3]);
+        assert_eq!(set.into_vec(), vec![1, 2, 3, 5]);
+    }
+}
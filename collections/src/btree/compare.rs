@@ -0,0 +1,87 @@
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+
+/// Defines the order of keys in [Map](super::Map) and [Set](super::Set)
+///
+/// A comparator is a type rather than a value, so the order is fixed for
+/// the type of a collection and does not need to be stored or serialized
+pub trait Compare<T: ?Sized> {
+    fn compare(a: &T, b: &T) -> Ordering;
+}
+
+/// The natural order of keys given by their [Ord] implementation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Compare<T> for Natural {
+    #[inline]
+    fn compare(a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// The reversed order of the comparator `C`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Reverse<C = Natural>(PhantomData<C>);
+
+impl<T: ?Sized, C: Compare<T>> Compare<T> for Reverse<C> {
+    #[inline]
+    fn compare(a: &T, b: &T) -> Ordering {
+        C::compare(b, a)
+    }
+}
+
+/// Byte-wise order of strings ignoring the case of ASCII letters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IgnoreAsciiCase;
+
+impl<T: AsRef<[u8]> + ?Sized> Compare<T> for IgnoreAsciiCase {
+    fn compare(a: &T, b: &T) -> Ordering {
+        let a = a.as_ref().iter().map(u8::to_ascii_lowercase);
+        let b = b.as_ref().iter().map(u8::to_ascii_lowercase);
+        a.cmp(b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::btree::{Map, Set};
+    use core::ops::Bound;
+
+    #[test]
+    fn ignore_ascii_case() {
+        let mut map: Map<String, u32, IgnoreAsciiCase> = Map::with_comparator();
+        map.insert("Beta".to_owned(), 2);
+        map.insert("alpha".to_owned(), 1);
+        assert_eq!(map.insert("ALPHA".to_owned(), 3), Some(1));
+        assert_eq!(map.get("BETA"), Some(&2));
+        assert_eq!(map["alpha"], 3);
+        let keys: Vec<_> = map.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["alpha", "Beta"]);
+        let other: Map<String, u32, IgnoreAsciiCase> =
+            vec![("BETA".to_owned(), 2), ("Alpha".to_owned(), 3)].into_iter().collect();
+        assert_eq!(map, other);
+        assert_eq!(map.cmp(&other), Ordering::Equal);
+        let mut bigger = other.clone();
+        bigger.insert("gamma".to_owned(), 0);
+        assert!(map < bigger);
+    }
+
+    #[test]
+    fn reverse_order() {
+        let set: Set<u32, Reverse> = vec![1, 3, 2].into_iter().collect();
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![3, 2, 1]);
+        let other: Set<u32, Reverse> = vec![4, 2].into_iter().collect();
+        assert_eq!((&set | &other).into_vec(), vec![4, 3, 2, 1]);
+        assert_eq!((&set & &other).into_vec(), vec![2]);
+        let map: Map<u32, (), Reverse> = set.iter().map(|k| (*k, ())).collect();
+        // bounds follow the order of the map, so the start is the greater key
+        let range = (Bound::Included(3), Bound::Included(1));
+        let keys: Vec<_> = map.range(range).map(|(k, _)| *k).collect();
+        assert_eq!(keys, vec![3, 2, 1]);
+        // sets are ordered by their first keys in the order of the comparator
+        assert!(set > other);
+        assert_eq!(set, vec![2, 1, 3].into_iter().collect());
+    }
+}
@@ -0,0 +1,298 @@
+use super::{Compare, Layout, Map, Set};
+use super::layout::{Pages, Pos};
+use crate::record::Rec;
+use core::mem;
+
+/// A view into a single record of a [Map], which may either be vacant or occupied
+pub enum Entry<'m, K, V> {
+    Occupied(OccupiedEntry<'m, K, V>),
+    Vacant(VacantEntry<'m, K, V>),
+}
+
+pub struct OccupiedEntry<'m, K, V> {
+    records: &'m mut Pages<K, V>,
+    pos: Pos,
+    page_size: usize,
+}
+
+pub struct VacantEntry<'m, K, V> {
+    records: &'m mut Pages<K, V>,
+    key: K,
+    pos: Pos,
+    page_size: usize,
+}
+
+impl<K, V, C: Compare<K>, L: Layout> Map<K, V, C, L> {
+    /// Gets the entry for the key with a single binary search
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V> {
+        let pos = self.locate(&k);
+        let (records, page_size) = (&mut self.records, L::PAGE_SIZE);
+        match pos {
+            Ok(pos) => Entry::Occupied(OccupiedEntry { records, pos, page_size }),
+            Err(pos) => Entry::Vacant(VacantEntry { records, key: k, pos, page_size }),
+        }
+    }
+}
+
+impl<'m, K, V> Entry<'m, K, V> {
+    #[inline]
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'m mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'m mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    #[inline]
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'m mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let v = default(&e.key);
+                e.insert(v)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn or_default(self) -> &'m mut V
+    where V: Default
+    {
+        self.or_insert_with(V::default)
+    }
+
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut())
+        }
+        self
+    }
+}
+
+impl<'m, K, V> OccupiedEntry<'m, K, V> {
+    /// Returns the position of the record in the map
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.records.index(self.pos)
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.record().key()
+    }
+
+    #[inline]
+    pub fn get(&self) -> &V {
+        self.record().value()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        self.records[self.pos].value_mut()
+    }
+
+    #[inline]
+    pub fn into_mut(self) -> &'m mut V {
+        self.records[self.pos].value_mut()
+    }
+
+    #[inline]
+    pub fn insert(&mut self, mut v: V) -> V {
+        mem::swap(self.get_mut(), &mut v);
+        v
+    }
+
+    #[inline]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    #[inline]
+    pub fn remove_entry(self) -> (K, V) {
+        self.records.remove(self.pos, self.page_size).into_pair()
+    }
+
+    #[inline]
+    fn record(&self) -> &Rec<K, V> {
+        &self.records[self.pos]
+    }
+}
+
+impl<'m, K, V> VacantEntry<'m, K, V> {
+    /// Returns the position the record will be inserted at
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.records.index(self.pos)
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    #[inline]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    #[inline]
+    pub fn insert(self, v: V) -> &'m mut V {
+        let pos = self.records.insert(self.pos, (self.key, v).into(), self.page_size);
+        self.records[pos].value_mut()
+    }
+}
+
+/// A view into a single key of a [Set], which may either be vacant or occupied
+pub enum SetEntry<'s, K> {
+    Occupied(OccupiedSetEntry<'s, K>),
+    Vacant(VacantSetEntry<'s, K>),
+}
+
+pub struct OccupiedSetEntry<'s, K>(OccupiedEntry<'s, K, ()>);
+
+pub struct VacantSetEntry<'s, K>(VacantEntry<'s, K, ()>);
+
+impl<K, C: Compare<K>, L: Layout> Set<K, C, L> {
+    /// Gets the entry for the key with a single binary search
+    #[inline]
+    pub fn entry(&mut self, k: K) -> SetEntry<'_, K> {
+        match self.0.entry(k) {
+            Entry::Occupied(e) => SetEntry::Occupied(OccupiedSetEntry(e)),
+            Entry::Vacant(e) => SetEntry::Vacant(VacantSetEntry(e)),
+        }
+    }
+}
+
+impl<'s, K> SetEntry<'s, K> {
+    #[inline]
+    pub fn key(&self) -> &K {
+        match self {
+            SetEntry::Occupied(e) => e.key(),
+            SetEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    #[inline]
+    pub fn is_occupied(&self) -> bool {
+        matches!(self, SetEntry::Occupied(_))
+    }
+
+    #[inline]
+    pub fn or_insert(self) {
+        if let SetEntry::Vacant(e) = self {
+            e.insert()
+        }
+    }
+}
+
+impl<'s, K> OccupiedSetEntry<'s, K> {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.0.index()
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.0.key()
+    }
+
+    #[inline]
+    pub fn remove(self) -> K {
+        self.0.remove_entry().0
+    }
+}
+
+impl<'s, K> VacantSetEntry<'s, K> {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.0.index()
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.0.key()
+    }
+
+    #[inline]
+    pub fn into_key(self) -> K {
+        self.0.into_key()
+    }
+
+    #[inline]
+    pub fn insert(self) {
+        self.0.insert(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn map_entry_upsert() {
+        let mut map = Map::new();
+        *map.entry("a").or_insert(0) += 1;
+        *map.entry("a").or_insert(0) += 1;
+        *map.entry("b").or_default() += 5;
+        map.entry("b").and_modify(|v| *v *= 2).or_insert(0);
+        map.entry("c").and_modify(|v| *v *= 2).or_insert_with_key(|k| k.len() as u32);
+        assert_eq!(map.into_vec(), vec![("a", 2), ("b", 10), ("c", 1)]);
+    }
+
+    #[test]
+    fn map_entry_occupied_and_vacant() {
+        let mut map = Map::new();
+        map.insert(1, "one");
+        map.insert(3, "three");
+        match map.entry(2) {
+            Entry::Vacant(e) => {
+                assert_eq!(e.index(), 1);
+                assert_eq!(e.key(), &2);
+                e.insert("two");
+            }
+            Entry::Occupied(_) => unreachable!(),
+        }
+        match map.entry(3) {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.index(), 2);
+                assert_eq!(e.insert("THREE"), "three");
+                assert_eq!(e.remove_entry(), (3, "THREE"));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.into_vec(), vec![(1, "one"), (2, "two")]);
+    }
+
+    #[test]
+    fn set_entry() {
+        let mut set = Set::new();
+        set.entry(2).or_insert();
+        assert!(set.entry(2).is_occupied());
+        match set.entry(1) {
+            SetEntry::Vacant(e) => e.insert(),
+            SetEntry::Occupied(_) => unreachable!(),
+        }
+        match set.entry(2) {
+            SetEntry::Occupied(e) => assert_eq!(e.remove(), 2),
+            SetEntry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(set.into_vec(), vec![1]);
+    }
+}
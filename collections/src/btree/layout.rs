@@ -0,0 +1,808 @@
+use crate::record::Rec;
+use core::cmp::Ordering;
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::{Index, IndexMut};
+use core::slice;
+use std::vec;
+
+/// Defines how a [Map](super::Map) stores its sorted records
+///
+/// Records are kept in sorted pages of at most `PAGE_SIZE` records. The list of pages
+/// is the index layer: a lookup finds the page by a binary search over the last keys
+/// of pages and then the record inside the page, so an insertion or a removal shifts
+/// at most one page. A full page is split in halves and a page which drops below half
+/// of `PAGE_SIZE` is merged with a neighbour
+pub trait Layout {
+    /// The maximal number of records in a page
+    const PAGE_SIZE: usize;
+}
+
+/// A single page of all records, which gives the fastest lookups and iteration,
+/// but an insertion or a removal shifts all records after it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Flat;
+
+impl Layout for Flat {
+    const PAGE_SIZE: usize = usize::MAX;
+}
+
+/// Pages of at most 512 records, prefer it when the map is large and is modified often
+///
+/// Positional access like [Map::get_at](super::Map::get_at) and [Map::rank](super::Map::rank)
+/// sums the lengths of pages, so it takes time linear in the number of pages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Paged;
+
+impl Layout for Paged {
+    const PAGE_SIZE: usize = 512;
+}
+
+/// A position of a record as a page index and an index inside the page
+///
+/// The position past the last record is the start of the page after the last one
+pub(crate) type Pos = (usize, usize);
+
+/// Sorted records split in pages, none of the pages is empty
+#[derive(Clone)]
+pub(crate) struct Pages<K, V> {
+    pages: Vec<Vec<Rec<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> Pages<K, V> {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self { pages: Vec::new(), len: 0 }
+    }
+
+    /// Splits sorted records into half filled pages, a map which fits
+    /// in a single page keeps the records as they are
+    pub(crate) fn from_records(records: Vec<Rec<K, V>>, page_size: usize) -> Self {
+        let len = records.len();
+        if len <= page_size {
+            let pages = if len == 0 { Vec::new() } else { vec![records] };
+            return Self { pages, len };
+        }
+        let half = (page_size / 2).max(1);
+        let mut records = records.into_iter();
+        let mut pages: Vec<Vec<_>> = Vec::with_capacity(len / half + 1);
+        loop {
+            let page: Vec<_> = records.by_ref().take(half).collect();
+            match pages.last_mut() {
+                _ if page.is_empty() => break,
+                // the last records join the previous page to keep it at least half full
+                Some(last) if page.len() < half => last.extend(page),
+                _ => pages.push(page),
+            }
+        }
+        Self { pages, len }
+    }
+
+    pub(crate) fn into_records(mut self) -> Vec<Rec<K, V>> {
+        if self.pages.len() <= 1 {
+            return self.pages.pop().unwrap_or_default();
+        }
+        let mut records = Vec::with_capacity(self.len);
+        self.pages.into_iter().for_each(|page| records.extend(page));
+        records
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.pages.clear();
+        self.len = 0;
+    }
+
+    #[inline]
+    pub(crate) fn pages(&self) -> &[Vec<Rec<K, V>>] {
+        &self.pages
+    }
+
+    #[inline]
+    pub(crate) fn first(&self) -> Option<&Rec<K, V>> {
+        self.pages.first()?.first()
+    }
+
+    #[inline]
+    pub(crate) fn last(&self) -> Option<&Rec<K, V>> {
+        self.pages.last()?.last()
+    }
+
+    /// Returns the position past the last record
+    #[inline]
+    pub(crate) fn end(&self) -> Pos {
+        (self.pages.len(), 0)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, (p, i): Pos) -> Option<&Rec<K, V>> {
+        self.pages.get(p)?.get(i)
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self, (p, i): Pos) -> Option<&mut Rec<K, V>> {
+        self.pages.get_mut(p)?.get_mut(i)
+    }
+
+    /// Converts a position past the end of a page to the start of the next one
+    #[inline]
+    pub(crate) fn normalize(&self, pos: Pos) -> Pos {
+        normalize(&self.pages, pos)
+    }
+
+    /// Returns the position of the record before the position
+    #[inline]
+    pub(crate) fn prev(&self, pos: Pos) -> Option<Pos> {
+        prev(&self.pages, pos)
+    }
+
+    /// Returns the number of records before the position
+    pub(crate) fn index(&self, (p, i): Pos) -> usize {
+        match self.pages.len() {
+            1 => i,
+            _ => self.pages[..p].iter().map(Vec::len).sum::<usize>() + i,
+        }
+    }
+
+    /// Returns the position of the record with the index, or a position
+    /// past the end if the index is out of bounds
+    pub(crate) fn pos(&self, mut index: usize) -> Pos {
+        for (p, page) in self.pages.iter().enumerate() {
+            if index < page.len() {
+                return (p, index);
+            }
+            index -= page.len();
+        }
+        (self.pages.len(), index)
+    }
+
+    /// Finds the page which contains the record or should contain it and the
+    /// position of the record, or the position where it should be inserted
+    pub(crate) fn search<F>(&self, mut f: F) -> Result<Pos, Pos>
+    where F: FnMut(&Rec<K, V>) -> Ordering
+    {
+        let p = match self.pages.len() {
+            0 => return Err((0, 0)),
+            1 => 0,
+            n => {
+                let p = self.pages.partition_point(|page| f(&page[page.len() - 1]).is_lt());
+                p.min(n - 1)
+            }
+        };
+        match self.pages[p].binary_search_by(f) {
+            Ok(i) => Ok((p, i)),
+            Err(i) => Err((p, i)),
+        }
+    }
+
+    /// Inserts the record at the position, splits the page in halves if it overflows
+    /// and returns the position of the record after the split
+    pub(crate) fn insert(&mut self, (p, i): Pos, r: Rec<K, V>, page_size: usize) -> Pos {
+        self.len += 1;
+        let page = match self.pages.get_mut(p) {
+            Some(page) => page,
+            None => {
+                self.pages.push(vec![r]);
+                return (self.pages.len() - 1, 0);
+            }
+        };
+        page.insert(i, r);
+        if page.len() <= page_size {
+            return (p, i);
+        }
+        let half = page.len() / 2;
+        let tail = page.split_off(half);
+        self.pages.insert(p + 1, tail);
+        if i < half { (p, i) } else { (p + 1, i - half) }
+    }
+
+    /// Removes the record at the position and merges its page with a neighbour if it is underfull
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no record at the position
+    pub(crate) fn remove(&mut self, (p, i): Pos, page_size: usize) -> Rec<K, V> {
+        let r = self.pages[p].remove(i);
+        self.len -= 1;
+        if self.pages[p].len() < page_size / 2 {
+            self.merge(p, page_size);
+        }
+        r
+    }
+
+    /// Appends records with keys greater than all stored keys
+    pub(crate) fn append(&mut self, records: Vec<Rec<K, V>>, page_size: usize) {
+        let boundary = self.pages.len();
+        let tail = Self::from_records(records, page_size);
+        self.len += tail.len;
+        self.pages.extend(tail.pages);
+        // only the pages around the boundary may be underfull
+        if boundary > 0 && boundary < self.pages.len() {
+            let underfull = |page: &Vec<_>| page.len() < page_size / 2;
+            if underfull(&self.pages[boundary - 1]) || underfull(&self.pages[boundary]) {
+                self.merge(boundary - 1, page_size);
+            }
+        }
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut f: F, page_size: usize)
+    where F: FnMut(&mut Rec<K, V>) -> bool
+    {
+        self.pages.iter_mut().for_each(|page| page.retain_mut(&mut f));
+        self.rebalance(page_size);
+    }
+
+    /// Removes the records for which `f` returns `true` and returns them in order
+    pub(crate) fn drain_filter<F>(&mut self, mut f: F, page_size: usize) -> Vec<Rec<K, V>>
+    where F: FnMut(&mut Rec<K, V>) -> bool
+    {
+        let mut removed = Vec::new();
+        for page in &mut self.pages {
+            let records = mem::take(page);
+            page.reserve(records.len());
+            for mut r in records {
+                match f(&mut r) {
+                    true => removed.push(r),
+                    false => page.push(r),
+                }
+            }
+        }
+        self.rebalance(page_size);
+        removed
+    }
+
+    /// Merges an underfull page with a neighbour, and splits the result in halves
+    /// if it overflows, so every page but a single one is at least half full
+    fn merge(&mut self, p: usize, page_size: usize) {
+        let q = match p {
+            _ if p + 1 < self.pages.len() => p,
+            0 => {
+                if self.pages[0].is_empty() {
+                    self.pages.clear();
+                }
+                return;
+            }
+            _ => p - 1,
+        };
+        let next = self.pages.remove(q + 1);
+        let page = &mut self.pages[q];
+        page.extend(next);
+        if page.len() > page_size {
+            let tail = page.split_off(page.len() / 2);
+            self.pages.insert(q + 1, tail);
+        }
+    }
+
+    /// Drops empty pages after a bulk removal and splits the records
+    /// in pages again if some of the pages are underfull
+    fn rebalance(&mut self, page_size: usize) {
+        self.pages.retain(|page| !page.is_empty());
+        self.len = self.pages.iter().map(Vec::len).sum();
+        if self.pages.len() > 1 && self.pages.iter().any(|page| page.len() < page_size / 2) {
+            let records = mem::take(self).into_records();
+            *self = Self::from_records(records, page_size);
+        }
+    }
+
+    /// Iterates over the records between two normalized positions
+    #[inline]
+    pub(crate) fn iter(&self, start: Pos, end: Pos) -> Records<'_, K, V> {
+        records(&self.pages, start, end)
+    }
+
+    /// Iterates mutably over the records between two normalized positions
+    pub(crate) fn iter_mut(&mut self, (sp, si): Pos, (ep, ei): Pos) -> RecordsMut<'_, K, V> {
+        let len = count(&self.pages, (sp, si), (ep, ei));
+        if sp == ep {
+            let front = self.pages.get_mut(sp).map(|page| &mut page[si..ei]).unwrap_or_default();
+            return PageIter::new(front.iter_mut(), [].iter_mut(), [].iter_mut(), len);
+        }
+        let (first, rest) = self.pages[sp..].split_first_mut().expect("start is before end");
+        let (pages, rest) = rest.split_at_mut(ep - sp - 1);
+        let back = rest.first_mut().map(|page| &mut page[..ei]).unwrap_or_default();
+        PageIter::new(first[si..].iter_mut(), pages.iter_mut(), back.iter_mut(), len)
+    }
+}
+
+impl<K, V> Default for Pages<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Index<Pos> for Pages<K, V> {
+    type Output = Rec<K, V>;
+
+    #[inline]
+    fn index(&self, (p, i): Pos) -> &Rec<K, V> {
+        &self.pages[p][i]
+    }
+}
+
+impl<K, V> IndexMut<Pos> for Pages<K, V> {
+    #[inline]
+    fn index_mut(&mut self, (p, i): Pos) -> &mut Rec<K, V> {
+        &mut self.pages[p][i]
+    }
+}
+
+impl<K, V> IntoIterator for Pages<K, V> {
+    type Item = Rec<K, V>;
+    type IntoIter = IntoRecords<K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        let empty = Vec::new().into_iter();
+        PageIter::new(empty, self.pages.into_iter(), Vec::new().into_iter(), self.len)
+    }
+}
+
+/// Converts a position past the end of a page to the start of the next one
+#[inline]
+pub(crate) fn normalize<K, V>(pages: &[Vec<Rec<K, V>>], (p, i): Pos) -> Pos {
+    match pages.get(p) {
+        Some(page) if i >= page.len() => (p + 1, 0),
+        _ => (p, i),
+    }
+}
+
+/// Returns the position of the record before the position
+#[inline]
+pub(crate) fn prev<K, V>(pages: &[Vec<Rec<K, V>>], (p, i): Pos) -> Option<Pos> {
+    match i {
+        0 => Some((p.checked_sub(1)?, pages[p - 1].len() - 1)),
+        _ => Some((p, i - 1)),
+    }
+}
+
+/// Returns the number of records between two normalized positions
+fn count<K, V>(pages: &[Vec<Rec<K, V>>], (sp, si): Pos, (ep, ei): Pos) -> usize {
+    match sp == ep {
+        true => ei - si,
+        false => pages[sp..ep].iter().map(Vec::len).sum::<usize>() - si + ei,
+    }
+}
+
+/// Iterates over the records between two normalized positions
+pub(crate) fn records<K, V>(
+    pages: &[Vec<Rec<K, V>>],
+    (sp, si): Pos,
+    (ep, ei): Pos,
+) -> Records<'_, K, V> {
+    let len = count(pages, (sp, si), (ep, ei));
+    if sp == ep {
+        let front = pages.get(sp).map(|page| &page[si..ei]).unwrap_or_default();
+        return PageIter::new(front.iter(), [].iter(), [].iter(), len);
+    }
+    let back = pages.get(ep).map(|page| &page[..ei]).unwrap_or_default();
+    PageIter::new(pages[sp][si..].iter(), pages[sp + 1..ep].iter(), back.iter(), len)
+}
+
+pub(crate) type Records<'i, K, V> =
+    PageIter<slice::Iter<'i, Vec<Rec<K, V>>>, slice::Iter<'i, Rec<K, V>>>;
+
+pub(crate) type RecordsMut<'i, K, V> =
+    PageIter<slice::IterMut<'i, Vec<Rec<K, V>>>, slice::IterMut<'i, Rec<K, V>>>;
+
+pub(crate) type IntoRecords<K, V> =
+    PageIter<vec::IntoIter<Vec<Rec<K, V>>>, vec::IntoIter<Rec<K, V>>>;
+
+/// Iterates over the records of the pages `O` between the partial pages at the front and the back
+pub(crate) struct PageIter<O, I> {
+    front: I,
+    pages: O,
+    back: I,
+    len: usize,
+}
+
+impl<O, I> PageIter<O, I> {
+    #[inline]
+    fn new(front: I, pages: O, back: I, len: usize) -> Self {
+        Self { front, pages, back, len }
+    }
+}
+
+impl<K, V> IntoRecords<K, V> {
+    /// Iterates over the records taken out of the pages
+    #[inline]
+    pub(crate) fn from_vec(records: Vec<Rec<K, V>>) -> Self {
+        let len = records.len();
+        PageIter::new(records.into_iter(), Vec::new().into_iter(), Vec::new().into_iter(), len)
+    }
+}
+
+impl<O, I> Iterator for PageIter<O, I>
+where
+    O: Iterator,
+    O::Item: IntoIterator<IntoIter = I, Item = I::Item>,
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(r) = self.front.next() {
+                self.len -= 1;
+                return Some(r);
+            }
+            match self.pages.next() {
+                Some(page) => self.front = page.into_iter(),
+                None => {
+                    let r = self.back.next()?;
+                    self.len -= 1;
+                    return Some(r);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<O, I> DoubleEndedIterator for PageIter<O, I>
+where
+    O: DoubleEndedIterator,
+    O::Item: IntoIterator<IntoIter = I, Item = I::Item>,
+    I: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(r) = self.back.next_back() {
+                self.len -= 1;
+                return Some(r);
+            }
+            match self.pages.next_back() {
+                Some(page) => self.back = page.into_iter(),
+                None => {
+                    let r = self.front.next_back()?;
+                    self.len -= 1;
+                    return Some(r);
+                }
+            }
+        }
+    }
+}
+
+impl<O, I> ExactSizeIterator for PageIter<O, I>
+where
+    O: Iterator,
+    O::Item: IntoIterator<IntoIter = I, Item = I::Item>,
+    I: Iterator,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<O, I> FusedIterator for PageIter<O, I>
+where
+    O: Iterator,
+    O::Item: IntoIterator<IntoIter = I, Item = I::Item>,
+    I: Iterator,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::btree::{Dedup, Entry, Map, Natural, Set};
+    use core::ops::Bound;
+    use random::Source;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::hash::{Hash, Hasher};
+
+    /// Pages small enough to be split and merged by short tests
+    struct Small;
+
+    impl Layout for Small {
+        const PAGE_SIZE: usize = 8;
+    }
+
+    /// Runs each generic test against every layout
+    macro_rules! layout_tests {
+        ($($name:ident),* $(,)?) => {
+            mod flat {
+                $(#[test] fn $name() { super::$name::<super::Flat>() })*
+            }
+
+            mod paged {
+                $(#[test] fn $name() { super::$name::<super::Paged>() })*
+            }
+
+            mod small {
+                $(#[test] fn $name() { super::$name::<super::Small>() })*
+            }
+        };
+    }
+
+    layout_tests!(
+        matches_std_btree_map,
+        merges_pages_after_removals,
+        positional_access,
+        entries,
+        mutable_iteration,
+        retain_and_drain_filter,
+        bulk_operations,
+        cursors,
+        standard_traits,
+        sets,
+    );
+
+    type TestMap<L> = Map<u32, u32, Natural, L>;
+
+    /// Deterministic pseudo-random keys below `max`
+    fn keys(seed: u64, n: usize, max: u64) -> Vec<u32> {
+        let mut source = random::default(seed);
+        (0..n).map(|_| (source.read_u64() % max) as u32).collect()
+    }
+
+    fn hash<T: Hash>(t: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        t.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Checks that the length matches the pages, the pages are bounded
+    /// and all but a single page are at least half full
+    fn half_full<K, V, C, L: Layout>(map: &Map<K, V, C, L>) -> bool {
+        let pages = map.records.pages();
+        let min = if pages.len() > 1 { L::PAGE_SIZE / 2 } else { 1 };
+        let len: usize = pages.iter().map(Vec::len).sum();
+        len == map.len() && pages.iter().all(|p| (min..=L::PAGE_SIZE).contains(&p.len()))
+    }
+
+    fn matches_std_btree_map<L: Layout>() {
+        let mut map = TestMap::<L>::default();
+        let mut model = BTreeMap::new();
+        for (n, k) in keys(1, 5_000, 2_000).into_iter().enumerate() {
+            let n = n as u32;
+            match n % 4 {
+                0 => assert_eq!(map.remove(&k), model.remove(&k)),
+                1 => {
+                    *map.entry(k).or_insert(0) += n;
+                    *model.entry(k).or_insert(0) += n;
+                }
+                _ => assert_eq!(map.insert(k, n), model.insert(k, n)),
+            }
+        }
+        assert!(half_full(&map));
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+        assert!(map.iter().rev().eq(model.iter().rev()));
+        for k in keys(2, 200, 2_100) {
+            assert_eq!(map.get(&k), model.get(&k));
+            assert_eq!(map.rank(&k), model.range(..k).count());
+            assert_eq!(map.floor(&k), model.range(..=k).next_back());
+            assert_eq!(map.ceiling(&k), model.range(k..).next());
+            let (a, b) = (k.min(1_000), k.max(1_000));
+            assert_eq!(map.range(a..b).len(), model.range(a..b).count());
+            assert!(map.range(a..b).eq(model.range(a..b)));
+            assert!(map.range(a..=b).rev().eq(model.range(a..=b).rev()));
+            assert!(map.range(..a).eq(model.range(..a)));
+            assert!(map.range((Bound::Excluded(b), Bound::Unbounded)).eq(model.range(b + 1..)));
+        }
+        assert_eq!(map.first(), model.iter().next());
+        assert_eq!(map.last(), model.iter().next_back());
+        while !model.is_empty() {
+            assert_eq!(map.pop_first(), model.pop_first());
+            assert_eq!(map.pop_last(), model.pop_last());
+            assert!(half_full(&map));
+        }
+        assert!(map.is_empty() && map.records.pages().is_empty());
+    }
+
+    fn merges_pages_after_removals<L: Layout>() {
+        let mut map: TestMap<L> = (0..2_000).map(|k| (k, k)).collect();
+        assert!(half_full(&map));
+        let pages = map.records.pages().len();
+        for k in (0..2_000).filter(|k| k % 10 != 0) {
+            assert_eq!(map.remove(&k), Some(k));
+            assert!(half_full(&map));
+        }
+        assert_eq!(map.len(), 200);
+        assert!(map.records.pages().len() <= pages.div_ceil(5));
+        assert!(map.keys().copied().eq((0..2_000).step_by(10)));
+    }
+
+    fn positional_access<L: Layout>() {
+        let mut map: TestMap<L> = (0..100).map(|k| (k * 2, k)).collect();
+        let mut model: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        for i in keys(3, 50, 100) {
+            let i = i as usize % model.len();
+            assert_eq!(map.get_at(i), model.get(i).map(|(k, v)| (k, v)));
+            assert_eq!(map.index_of(&model[i].0), Some(i));
+            if let Some((_, v)) = map.get_at_mut(i) {
+                *v += 1;
+            }
+            model[i].1 += 1;
+            assert_eq!(map.remove_at(i), model.remove(i));
+            assert!(half_full(&map));
+        }
+        assert_eq!(map.get_at(model.len()), None);
+        let len = model.len();
+        for (a, b) in [(0, 0), (0, len), (3, 17), (len - 5, len), (len, len)] {
+            assert!(map.range_at(a..b).eq(model[a..b].iter().map(|(k, v)| (k, v))));
+        }
+        assert!(map.range_at(..).rev().eq(model.iter().rev().map(|(k, v)| (k, v))));
+    }
+
+    fn entries<L: Layout>() {
+        let mut map = TestMap::<L>::default();
+        for k in (0..100).rev().chain(100..200) {
+            match map.entry(k) {
+                Entry::Vacant(e) => {
+                    let index = e.index();
+                    assert_eq!(*e.insert(k), k);
+                    assert_eq!(map.index_of(&k), Some(index));
+                }
+                Entry::Occupied(_) => unreachable!(),
+            }
+        }
+        assert!(half_full(&map));
+        for k in (0..200).step_by(3) {
+            let rank = map.rank(&k);
+            match map.entry(k) {
+                Entry::Occupied(mut e) => {
+                    assert_eq!(e.index(), rank);
+                    assert_eq!(e.insert(k + 1), k);
+                    assert_eq!(e.remove_entry(), (k, k + 1));
+                }
+                Entry::Vacant(_) => unreachable!(),
+            }
+            assert!(half_full(&map));
+        }
+        assert!(map.keys().copied().eq((0..200).filter(|k| k % 3 != 0)));
+        *map.get_or_create_mut(0, || 7) += 1;
+        assert_eq!(map.get(&0), Some(&8));
+    }
+
+    fn mutable_iteration<L: Layout>() {
+        let mut map: TestMap<L> = (0..100).map(|k| (k, 0)).collect();
+        map.iter_mut().for_each(|(k, v)| *v += k);
+        map.values_mut().rev().take(10).for_each(|v| *v = 0);
+        map.range_mut(20..30).for_each(|(_, v)| *v *= 2);
+        for (_, v) in &mut map {
+            *v += 1;
+        }
+        let expected = |k: u32| match k {
+            20..=29 => k * 2 + 1,
+            90.. => 1,
+            _ => k + 1,
+        };
+        assert!(map.iter().all(|(k, v)| *v == expected(*k)));
+        let mut values = map.values_mut();
+        assert_eq!(values.len(), 100);
+        assert_eq!(values.next_back(), Some(&mut 1));
+        assert_eq!(values.next(), Some(&mut 1));
+        assert_eq!(values.len(), 98);
+        let mut rest = map.into_iter().skip(95);
+        assert_eq!(rest.len(), 5);
+        assert_eq!(rest.next_back(), Some((99, 1)));
+        assert_eq!(rest.next(), Some((95, 1)));
+    }
+
+    fn retain_and_drain_filter<L: Layout>() {
+        let mut map: TestMap<L> = (0..1_000).map(|k| (k, k)).collect();
+        map.retain(|k, v| {
+            *v *= 2;
+            k % 3 != 0
+        });
+        assert!(half_full(&map));
+        assert!(map.iter().all(|(k, v)| k % 3 != 0 && *v == k * 2));
+        let drained: Vec<_> = map.drain_filter(|k, _| k % 100 < 50).map(|(k, _)| k).collect();
+        assert!(drained.into_iter().eq((0..1_000).filter(|k| k % 3 != 0 && k % 100 < 50)));
+        assert!(half_full(&map));
+        assert!(map.keys().copied().eq((0..1_000).filter(|k| k % 3 != 0 && k % 100 >= 50)));
+        map.retain(|_, _| false);
+        assert!(map.is_empty() && half_full(&map));
+    }
+
+    fn bulk_operations<L: Layout>() {
+        let mut map: TestMap<L> = keys(4, 500, 1_000).into_iter().map(|k| (k, 0)).collect();
+        let mut model: BTreeMap<_, _> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        let more: Vec<_> = keys(5, 500, 2_000).into_iter().map(|k| (k, 1)).collect();
+        map.extend(more.iter().copied());
+        model.extend(more);
+        assert!(half_full(&map));
+        assert!(map.iter().eq(model.iter()));
+        let mut other: TestMap<L> = (0..3_000).step_by(7).map(|k| (k, 2)).collect();
+        map.append(&mut other);
+        model.extend((0..3_000).step_by(7).map(|k| (k, 2)));
+        assert!(other.is_empty() && half_full(&map));
+        assert!(map.iter().eq(model.iter()));
+        for n in [1, 3, 300] {
+            let start = map.last().map_or(0, |(k, _)| k + 1);
+            let tail: Vec<_> = (start..start + n).map(|k| (k, 3)).collect();
+            map.extend_with(tail.iter().copied(), Dedup::KeepFirst);
+            model.extend(tail);
+            assert!(half_full(&map));
+        }
+        assert!(map.iter().eq(model.iter()));
+    }
+
+    fn cursors<L: Layout>() {
+        let map: TestMap<L> = (0..100).map(|k| (k * 2, k)).collect();
+        let mut cur = map.lower_bound(&0);
+        for i in 0..100 {
+            assert_eq!(cur.index(), i as usize);
+            assert_eq!(cur.key(), Some(&(i * 2)));
+            assert_eq!(cur.iter().len(), 100 - i as usize);
+            assert!(cur.move_next());
+            assert_eq!(cur.peek_prev(), Some((&(i * 2), &i)));
+        }
+        assert_eq!(cur.get(), None);
+        assert!(!cur.move_next());
+        let mut moves = 0;
+        while cur.move_prev() {
+            moves += 1;
+        }
+        assert_eq!((moves, cur.index(), cur.peek_prev()), (100, 0, None));
+        assert_eq!(map.upper_bound(&50).key(), Some(&52));
+        assert_eq!(map.lower_bound(&51).index(), 26);
+        assert!(map.lower_bound(&51).iter().eq(map.range(51..)));
+        assert_eq!(map.upper_bound(&198).get(), None);
+    }
+
+    fn standard_traits<L: Layout>() {
+        let pairs: Vec<_> = keys(6, 300, 1_000).into_iter().map(|k| (k, k % 7)).collect();
+        let map: TestMap<L> = pairs.iter().copied().collect();
+        let flat: Map<u32, u32> = pairs.into_iter().collect();
+        assert_eq!(map.clone(), map);
+        assert_eq!(map.clone().into_layout::<Flat>(), flat);
+        assert_eq!(flat.clone().into_layout::<L>(), map);
+        assert_eq!(hash(&map), hash(&flat));
+        assert_eq!(format!("{:?}", map), format!("{:?}", flat));
+        let (k, v) = map.last().unwrap();
+        assert_eq!(map[k], *v);
+        let mut other = map.clone();
+        other.insert(1_000, 0);
+        assert!(map < other && map != other);
+        assert_eq!(map.cmp(&map.clone()), Ordering::Equal);
+    }
+
+    fn sets<L: Layout>() {
+        let a: Set<u32, Natural, L> = keys(7, 300, 600).into_iter().collect();
+        let b: Set<u32, Natural, L> = keys(8, 300, 600).into_iter().collect();
+        let ma: BTreeSet<_> = a.iter().copied().collect();
+        let mb: BTreeSet<_> = b.iter().copied().collect();
+        assert!(a.union(&b).eq(ma.union(&mb)));
+        assert!(a.intersection(&b).eq(ma.intersection(&mb)));
+        assert!(a.symmetric_difference(&b).eq(ma.symmetric_difference(&mb)));
+        assert!((&a - &b).iter().eq(ma.difference(&mb)));
+        let mut set = &a | &b;
+        let mut model: BTreeSet<_> = ma.union(&mb).copied().collect();
+        assert!(half_full(&set.0));
+        for k in keys(9, 300, 700) {
+            assert_eq!(set.rank(&k), model.range(..k).count());
+            match k % 2 {
+                0 => assert_eq!(set.remove(&k), model.remove(&k)),
+                _ => assert_eq!(set.insert(k), model.insert(k)),
+            }
+            assert!(half_full(&set.0));
+        }
+        assert!(set.iter().eq(model.iter()));
+        assert_eq!(set.get_at(10), model.iter().nth(10));
+        let k = set.remove_at(10);
+        assert!(model.remove(&k) && !set.contains(&k));
+        set.retain(|k| k % 3 != 0);
+        model.retain(|k| k % 3 != 0);
+        let drained: Vec<_> = set.drain_filter(|k| k % 5 == 0).collect();
+        assert!(drained.iter().eq(model.iter().filter(|k| *k % 5 == 0)));
+        model.retain(|k| k % 5 != 0);
+        assert!(half_full(&set.0));
+        assert_eq!(hash(&set), hash(&set.clone().into_layout::<Flat>()));
+        assert_eq!(set.into_vec(), model.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1104 @@
+use crate::record::Rec;
+use core::mem;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Bound, Index, RangeBounds};
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+#[cfg(feature = "serde_derive")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+mod compare;
+pub use compare::{Compare, Natural, Reverse, IgnoreAsciiCase};
+
+mod entry;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use entry::{SetEntry, OccupiedSetEntry, VacantSetEntry};
+
+mod bulk;
+pub use bulk::Dedup;
+
+mod algebra;
+pub use algebra::{Union, Intersection, Difference, SymmetricDifference};
+
+mod layout;
+pub use layout::{Layout, Flat, Paged};
+use layout::{Pages, Pos, Records, RecordsMut, IntoRecords};
+
+/// A map based on both [B-Tree] and [Vec]
+///
+/// Keys are kept in the order defined by the comparator `C`, see [Compare],
+/// and records are stored as defined by the layout `L`, see [Layout]
+pub struct Map<K, V, C = Natural, L = Flat> {
+    records: Pages<K, V>,
+    order: PhantomData<(C, L)>,
+}
+
+/// A [Map] which stores its records in pages of bounded size
+pub type PagedMap<K, V, C = Natural> = Map<K, V, C, Paged>;
+
+impl<K, V, C, L> Default for Map<K, V, C, L> {
+    #[inline]
+    fn default() -> Self {
+        Map::with_comparator()
+    }
+}
+
+impl<K: Clone, V: Clone, C, L> Clone for Map<K, V, C, L> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self { records: self.records.clone(), order: PhantomData }
+    }
+}
+
+/// Keys are compared by the comparator `C`, so the maps are equal when their keys
+/// are equivalent in the order of the map
+impl<K, V: PartialEq, C: Compare<K>, L> PartialEq for Map<K, V, C, L> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        let eq = |((ka, va), (kb, vb)): ((&K, &V), (&K, &V))| {
+            C::compare(ka, kb).is_eq() && va == vb
+        };
+        self.len() == other.len() && self.iter().zip(other).all(eq)
+    }
+}
+
+impl<K, V: Eq, C: Compare<K>, L> Eq for Map<K, V, C, L> {}
+
+/// Compares the records lexicographically with keys in the order of the comparator `C`
+impl<K, V: PartialOrd, C: Compare<K>, L> PartialOrd for Map<K, V, C, L> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.cmp_by(other, V::partial_cmp)
+    }
+}
+
+impl<K, V: Ord, C: Compare<K>, L> Ord for Map<K, V, C, L> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_by(other, |a, b| Some(a.cmp(b))).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Only maps in the natural order are hashable, because a comparator may treat
+/// keys with different hashes as equal
+impl<K: Hash, V: Hash, L> Hash for Map<K, V, Natural, L> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        self.inner_iter().for_each(|r| r.hash(state))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C, L> fmt::Debug for Map<K, V, C, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Serializes the records as a flat sequence regardless of the layout
+#[cfg(feature = "serde_derive")]
+impl<K: Serialize, V: Serialize, C, L> Serialize for Map<K, V, C, L> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.inner_iter())
+    }
+}
+
+#[cfg(feature = "serde_derive")]
+impl<'de, K, V, C, L: Layout> Deserialize<'de> for Map<K, V, C, L>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Self::from_sorted)
+    }
+}
+
+impl<K, Q: ?Sized, V, C, L> Index<&Q> for Map<K, V, C, L>
+where
+    K: Borrow<Q>,
+    C: Compare<K> + Compare<Q>,
+{
+    type Output = V;
+
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map
+    #[inline]
+    fn index(&self, k: &Q) -> &V {
+        self.get(k).expect("no entry found for key")
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for Map<K, V> {
+    #[inline]
+    fn from(pairs: [(K, V); N]) -> Self {
+        Self::from_iter(pairs)
+    }
+}
+
+impl<K, V> Map<K, V> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_comparator()
+    }
+}
+
+impl<K, V, C, L> Map<K, V, C, L> {
+    /// Creates an empty map ordered by the comparator `C` with the layout `L`
+    #[inline]
+    pub const fn with_comparator() -> Self {
+        Self { records: Pages::new(), order: PhantomData }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.records.clear()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.len() == 0
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+}
+
+impl<K, V, C, L: Layout> Map<K, V, C, L> {
+    /// Records must be sorted and unique
+    #[inline]
+    fn from_sorted(records: Vec<Rec<K, V>>) -> Self {
+        Self { records: Pages::from_records(records, L::PAGE_SIZE), order: PhantomData }
+    }
+
+    /// Moves the records into a map with another layout
+    #[inline]
+    pub fn into_layout<M: Layout>(self) -> Map<K, V, C, M> {
+        Map::from_sorted(self.records.into_records())
+    }
+}
+
+impl<K, V, C, L> Map<K, V, C, L> {
+    /// Returns the record at the position `i` in key order
+    #[inline]
+    pub fn get_at(&self, i: usize) -> Option<(&K, &V)> {
+        self.records.get(self.records.pos(i)).map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn get_at_mut(&mut self, i: usize) -> Option<(&K, &mut V)> {
+        let pos = self.records.pos(i);
+        self.records.get_mut(pos).map(Rec::as_pair_mut)
+    }
+
+    /// Iterates over the records within the range of positions
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds
+    pub fn range_at<R: RangeBounds<usize>>(&self, range: R) -> Range<'_, K, V> {
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(start <= end, "range starts at {} but ends at {}", start, end);
+        assert!(end <= self.len(), "range end {} is out of bounds of length {}", end, self.len());
+        let start = self.records.normalize(self.records.pos(start));
+        let end = self.records.normalize(self.records.pos(end));
+        Range(self.records.iter(start, end))
+    }
+}
+
+impl<K, V, C, L: Layout> Map<K, V, C, L> {
+    /// Removes the record at the position `i` in key order
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds
+    #[inline]
+    pub fn remove_at(&mut self, i: usize) -> (K, V) {
+        assert!(i < self.len(), "index {} is out of bounds of length {}", i, self.len());
+        let pos = self.records.pos(i);
+        self.records.remove(pos, L::PAGE_SIZE).into_pair()
+    }
+}
+
+impl<K, V, C: Compare<K>, L: Layout> Map<K, V, C, L> {
+    pub fn insert(&mut self, k: K, mut v: V) -> Option<V> {
+        match self.locate(&k) {
+            Ok(pos) => {
+                mem::swap(&mut v, &mut self.records[pos].val);
+                Some(v)
+            }
+            Err(pos) => {
+                self.records.insert(pos, (k, v).into(), L::PAGE_SIZE);
+                None
+            }
+        }
+    }
+
+    pub fn get_or_create_mut<F>(&mut self, k: K, new: F) -> &mut V
+    where F: FnOnce() -> V
+    {
+        self.entry(k).or_insert_with(new)
+    }
+
+    pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let pos = self.locate(k).ok()?;
+        Some(self.records.remove(pos, L::PAGE_SIZE).val)
+    }
+}
+
+impl<K, V, C: Compare<K>, L> Map<K, V, C, L> {
+    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let pos = self.locate(k).ok()?;
+        self.records.get(pos).map(Rec::value)
+    }
+
+    pub fn get_mut<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let pos = self.locate(k).ok()?;
+        self.records.get_mut(pos).map(Rec::value_mut)
+    }
+
+    #[inline]
+    pub fn contains<Q: ?Sized>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.locate(k).is_ok()
+    }
+
+    pub fn range<Q: ?Sized, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = self.range_pos(&range);
+        Range(self.records.iter(start, end))
+    }
+
+    pub fn range_mut<Q: ?Sized, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = self.range_pos(&range);
+        RangeMut(self.records.iter_mut(start, end))
+    }
+
+    /// Returns the number of keys less than `k`
+    #[inline]
+    pub fn rank<Q: ?Sized>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.locate(k) {
+            Ok(pos) | Err(pos) => self.records.index(pos),
+        }
+    }
+
+    /// Returns the position of the key in key order
+    #[inline]
+    pub fn index_of<Q: ?Sized>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.locate(k).ok().map(|pos| self.records.index(pos))
+    }
+
+    /// Returns a cursor pointing to the first record with a key not less than `k`
+    pub fn lower_bound<Q: ?Sized>(&self, k: &Q) -> Cursor<'_, K, V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.locate(k) {
+            Ok(pos) | Err(pos) => self.cursor(pos),
+        }
+    }
+
+    /// Returns a cursor pointing to the first record with a key greater than `k`
+    pub fn upper_bound<Q: ?Sized>(&self, k: &Q) -> Cursor<'_, K, V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.locate(k) {
+            Ok((p, i)) => self.cursor((p, i + 1)),
+            Err(pos) => self.cursor(pos),
+        }
+    }
+
+    /// Returns the record with the greatest key less than or equal to `k`
+    pub fn floor<Q: ?Sized>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let pos = match self.locate(k) {
+            Ok(pos) => pos,
+            Err(pos) => self.records.prev(pos)?,
+        };
+        self.records.get(pos).map(Rec::as_pair)
+    }
+
+    /// Returns the record with the least key greater than or equal to `k`
+    pub fn ceiling<Q: ?Sized>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let pos = match self.locate(k) {
+            Ok(pos) | Err(pos) => self.records.normalize(pos),
+        };
+        self.records.get(pos).map(Rec::as_pair)
+    }
+
+    /// Compares the records lexicographically, keys by `C` and values by `f`
+    fn cmp_by<F>(&self, other: &Self, mut f: F) -> Option<Ordering>
+    where F: FnMut(&V, &V) -> Option<Ordering>
+    {
+        let mut other = other.iter();
+        for (ka, va) in self.iter() {
+            let (kb, vb) = match other.next() {
+                Some(r) => r,
+                None => return Some(Ordering::Greater),
+            };
+            match C::compare(ka, kb) {
+                Ordering::Equal => match f(va, vb)? {
+                    Ordering::Equal => {}
+                    ord => return Some(ord),
+                },
+                ord => return Some(ord),
+            }
+        }
+        match other.next() {
+            Some(_) => Some(Ordering::Less),
+            None => Some(Ordering::Equal),
+        }
+    }
+
+    /// Finds the position of the key, or the position where it should be inserted
+    #[inline]
+    fn locate<Q: ?Sized>(&self, k: &Q) -> Result<Pos, Pos>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.records.search(|r| C::compare(r.key.borrow(), k))
+    }
+
+    fn range_pos<Q: ?Sized, R>(&self, range: &R) -> (Pos, Pos)
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(k) => match self.locate(k) {
+                Ok(pos) | Err(pos) => pos,
+            },
+            Bound::Excluded(k) => match self.locate(k) {
+                Ok((p, i)) => (p, i + 1),
+                Err(pos) => pos,
+            },
+            Bound::Unbounded => (0, 0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => match self.locate(k) {
+                Ok((p, i)) => (p, i + 1),
+                Err(pos) => pos,
+            },
+            Bound::Excluded(k) => match self.locate(k) {
+                Ok(pos) | Err(pos) => pos,
+            },
+            Bound::Unbounded => self.records.end(),
+        };
+        let (start, end) = (self.records.normalize(start), self.records.normalize(end));
+        (start, end.max(start))
+    }
+}
+
+impl<K, V, C, L> Map<K, V, C, L> {
+    #[inline]
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.records.first().map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.records.last().map(Rec::as_pair)
+    }
+
+    #[inline]
+    fn cursor(&self, pos: Pos) -> Cursor<'_, K, V> {
+        let pos = self.records.normalize(pos);
+        let index = self.records.index(pos);
+        Cursor { pages: self.records.pages(), pos, index, len: self.len() }
+    }
+}
+
+impl<K, V, C, L: Layout> Map<K, V, C, L> {
+    #[inline]
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.records.remove((0, 0), L::PAGE_SIZE).into_pair())
+    }
+
+    #[inline]
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let pos = self.records.prev(self.records.end())?;
+        Some(self.records.remove(pos, L::PAGE_SIZE).into_pair())
+    }
+}
+
+pub(crate) type InnerIter<'i, K, V> = Records<'i, K, V>;
+
+/// Implements the iterator traits for a wrapper over an inner iterator of records
+macro_rules! impl_iter {
+    ($name:ident<$($gen:tt),*>, $item:ty, $map:expr) => {
+        impl<$($gen),*> Iterator for $name<$($gen),*> {
+            type Item = $item;
+
+            #[inline]
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next().map($map)
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        impl<$($gen),*> DoubleEndedIterator for $name<$($gen),*> {
+            #[inline]
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.0.next_back().map($map)
+            }
+        }
+
+        impl<$($gen),*> ExactSizeIterator for $name<$($gen),*> {
+            #[inline]
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+        }
+
+        impl<$($gen),*> FusedIterator for $name<$($gen),*> {}
+    };
+}
+
+pub struct MapIter<'i, K, V>(InnerIter<'i, K, V>);
+
+impl_iter!(MapIter<'i, K, V>, (&'i K, &'i V), Rec::as_pair);
+
+pub struct Range<'i, K, V>(InnerIter<'i, K, V>);
+
+impl_iter!(Range<'i, K, V>, (&'i K, &'i V), Rec::as_pair);
+
+pub struct RangeMut<'i, K, V>(RecordsMut<'i, K, V>);
+
+impl_iter!(RangeMut<'i, K, V>, (&'i K, &'i mut V), Rec::as_pair_mut);
+
+pub struct IterMut<'i, K, V>(RecordsMut<'i, K, V>);
+
+impl_iter!(IterMut<'i, K, V>, (&'i K, &'i mut V), Rec::as_pair_mut);
+
+/// A position in the ordered records of a [Map]
+///
+/// The cursor points either to a record or past the last one
+pub struct Cursor<'i, K, V> {
+    pages: &'i [Vec<Rec<K, V>>],
+    pos: Pos,
+    index: usize,
+    len: usize,
+}
+
+impl<K, V> Clone for Cursor<'_, K, V> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self { pages: self.pages, pos: self.pos, index: self.index, len: self.len }
+    }
+}
+
+impl<'i, K, V> Cursor<'i, K, V> {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[inline]
+    pub fn get(&self) -> Option<(&'i K, &'i V)> {
+        self.record().map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn key(&self) -> Option<&'i K> {
+        self.record().map(Rec::key)
+    }
+
+    #[inline]
+    pub fn value(&self) -> Option<&'i V> {
+        self.record().map(Rec::value)
+    }
+
+    #[inline]
+    pub fn peek_prev(&self) -> Option<(&'i K, &'i V)> {
+        let (p, i) = layout::prev(self.pages, self.pos)?;
+        Some(self.pages[p][i].as_pair())
+    }
+
+    /// Moves the cursor to the next record, returns `false` if it is already past the end
+    pub fn move_next(&mut self) -> bool {
+        if self.index < self.len {
+            self.index += 1;
+            self.pos = layout::normalize(self.pages, (self.pos.0, self.pos.1 + 1));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves the cursor to the previous record, returns `false` if it is already at the start
+    pub fn move_prev(&mut self) -> bool {
+        match layout::prev(self.pages, self.pos) {
+            Some(pos) => {
+                self.index -= 1;
+                self.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Iterates over the records starting from the current position
+    #[inline]
+    pub fn iter(&self) -> MapIter<'i, K, V> {
+        MapIter(layout::records(self.pages, self.pos, (self.pages.len(), 0)))
+    }
+
+    #[inline]
+    fn record(&self) -> Option<&'i Rec<K, V>> {
+        self.pages.get(self.pos.0)?.get(self.pos.1)
+    }
+}
+
+pub struct Keys<'i, K, V>(InnerIter<'i, K, V>);
+
+impl_iter!(Keys<'i, K, V>, &'i K, Rec::key);
+
+pub struct Values<'i, K, V>(InnerIter<'i, K, V>);
+
+impl_iter!(Values<'i, K, V>, &'i V, Rec::value);
+
+pub struct ValuesMut<'i, K, V>(RecordsMut<'i, K, V>);
+
+impl_iter!(ValuesMut<'i, K, V>, &'i mut V, Rec::value_mut);
+
+impl<K, V, C, L> Map<K, V, C, L> {
+    #[inline]
+    pub(crate) fn inner_iter(&self) -> InnerIter<'_, K, V> {
+        self.records.iter((0, 0), self.records.end())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> MapIter<'_, K, V> {
+        MapIter(self.inner_iter())
+    }
+
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.inner_iter())
+    }
+
+    #[inline]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.inner_iter())
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let end = self.records.end();
+        IterMut(self.records.iter_mut((0, 0), end))
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        let end = self.records.end();
+        ValuesMut(self.records.iter_mut((0, 0), end))
+    }
+
+    #[inline]
+    pub fn into_vec(self) -> Vec<(K, V)> {
+        self.records.into_records().into_iter().map(Rec::into_pair).collect()
+    }
+}
+
+impl<K, V, C, L: Layout> Map<K, V, C, L> {
+    /// Keeps only the records for which `f` returns `true`, compacting the map in one pass
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where F: FnMut(&K, &mut V) -> bool
+    {
+        self.records.retain(|r| f(&r.key, &mut r.val), L::PAGE_SIZE)
+    }
+
+    /// Removes the records for which `f` returns `true` in one pass and yields them in order
+    #[inline]
+    pub fn drain_filter<F>(&mut self, mut f: F) -> MapIntoIter<K, V>
+    where F: FnMut(&K, &mut V) -> bool
+    {
+        let removed = self.records.drain_filter(|r| f(&r.key, &mut r.val), L::PAGE_SIZE);
+        MapIntoIter(IntoRecords::from_vec(removed))
+    }
+}
+
+pub struct MapIntoIter<K, V>(IntoRecords<K, V>);
+
+impl_iter!(MapIntoIter<K, V>, (K, V), Rec::into_pair);
+
+impl<'i, K, V, C, L> IntoIterator for &'i Map<K, V, C, L> {
+    type Item = (&'i K, &'i V);
+    type IntoIter = MapIter<'i, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'i, K, V, C, L> IntoIterator for &'i mut Map<K, V, C, L> {
+    type Item = (&'i K, &'i mut V);
+    type IntoIter = IterMut<'i, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C, L> IntoIterator for Map<K, V, C, L> {
+    type Item = (K, V);
+    type IntoIter = MapIntoIter<K, V>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        MapIntoIter(self.records.into_iter())
+    }
+}
+
+/// A set based on [Map] with keys ordered by the comparator `C` and stored with the layout `L`
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "K: Serialize")))]
+#[cfg_attr(feature = "serde_derive", serde(bound(deserialize = "K: Deserialize<'de>, L: Layout")))]
+pub struct Set<K, C = Natural, L = Flat>(Map<K, (), C, L>);
+
+/// A [Set] which stores its keys in pages of bounded size
+pub type PagedSet<K, C = Natural> = Set<K, C, Paged>;
+
+impl<K, C, L> Default for Set<K, C, L> {
+    #[inline]
+    fn default() -> Self {
+        Set::with_comparator()
+    }
+}
+
+impl<K: Clone, C, L> Clone for Set<K, C, L> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, C: Compare<K>, L> PartialEq for Set<K, C, L> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K, C: Compare<K>, L> Eq for Set<K, C, L> {}
+
+impl<K, C: Compare<K>, L> PartialOrd for Set<K, C, L> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, C: Compare<K>, L> Ord for Set<K, C, L> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<K: Hash, L> Hash for Set<K, Natural, L> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<K: fmt::Debug, C, L> fmt::Debug for Set<K, C, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, const N: usize> From<[K; N]> for Set<K> {
+    #[inline]
+    fn from(keys: [K; N]) -> Self {
+        Self::from_iter(keys)
+    }
+}
+
+impl<K> Set<K> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_comparator()
+    }
+}
+
+impl<K, C, L> Set<K, C, L> {
+    /// Creates an empty set ordered by the comparator `C` with the layout `L`
+    #[inline]
+    pub const fn with_comparator() -> Self {
+        Self(Map::with_comparator())
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<K, C, L: Layout> Set<K, C, L> {
+    /// Moves the keys into a set with another layout
+    #[inline]
+    pub fn into_layout<M: Layout>(self) -> Set<K, C, M> {
+        Set(self.0.into_layout())
+    }
+}
+
+impl<K, C: Compare<K>, L: Layout> Set<K, C, L> {
+    #[inline]
+    pub fn insert(&mut self, k: K) -> bool {
+        self.0.insert(k, ()).is_none()
+    }
+
+    #[inline]
+    pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.0.remove(k).is_some()
+    }
+}
+
+impl<K, C: Compare<K>, L> Set<K, C, L> {
+    #[inline]
+    pub fn contains<Q: ?Sized>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.0.contains(k)
+    }
+
+    /// Returns the number of keys less than `k`
+    #[inline]
+    pub fn rank<Q: ?Sized>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.0.rank(k)
+    }
+
+    /// Returns the position of the key in key order
+    #[inline]
+    pub fn index_of<Q: ?Sized>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.0.index_of(k)
+    }
+}
+
+impl<K, C, L> Set<K, C, L> {
+    /// Returns the key at the position `i` in key order
+    #[inline]
+    pub fn get_at(&self, i: usize) -> Option<&K> {
+        self.0.get_at(i).map(|(k, _)| k)
+    }
+}
+
+impl<K, C, L: Layout> Set<K, C, L> {
+    /// Removes the key at the position `i` in key order
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds
+    #[inline]
+    pub fn remove_at(&mut self, i: usize) -> K {
+        self.0.remove_at(i).0
+    }
+}
+
+impl<K, C, L> Set<K, C, L> {
+    #[inline]
+    pub(crate) fn inner_iter(&self) -> InnerIter<'_, K, ()> {
+        self.0.inner_iter()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> SetIter<'_, K> {
+        SetIter(self.inner_iter())
+    }
+
+    #[inline]
+    pub fn into_vec(self) -> Vec<K> {
+        self.into_iter().collect()
+    }
+}
+
+impl<K, C, L: Layout> Set<K, C, L> {
+    /// Keeps only the keys for which `f` returns `true`, compacting the set in one pass
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where F: FnMut(&K) -> bool
+    {
+        self.0.retain(|k, _| f(k))
+    }
+
+    /// Removes the keys for which `f` returns `true` in one pass and yields them in order
+    pub fn drain_filter<F>(&mut self, mut f: F) -> SetIntoIter<K>
+    where F: FnMut(&K) -> bool
+    {
+        SetIntoIter(self.0.drain_filter(|k, _| f(k)).0)
+    }
+}
+
+pub struct SetIter<'i, K>(InnerIter<'i, K, ()>);
+
+impl_iter!(SetIter<'i, K>, &'i K, Rec::key);
+
+pub struct SetIntoIter<K>(IntoRecords<K, ()>);
+
+impl_iter!(SetIntoIter<K>, K, Rec::into_key);
+
+impl<'i, K, C, L> IntoIterator for &'i Set<K, C, L> {
+    type Item = &'i K;
+    type IntoIter = SetIter<'i, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, C, L> IntoIterator for Set<K, C, L> {
+    type Item = K;
+    type IntoIter = SetIntoIter<K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        SetIntoIter(self.0.into_iter().0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map() -> Map<u32, u32> {
+        let mut map = Map::new();
+        for i in (0..10).rev() {
+            map.insert(i * 10, i);
+        }
+        map
+    }
+
+    #[test]
+    fn range_by_bounds() {
+        let map = map();
+        let keys = |r: Range<'_, u32, u32>| r.map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys(map.range(20..40)), vec![20, 30]);
+        assert_eq!(keys(map.range(15..=40)), vec![20, 30, 40]);
+        assert_eq!(keys(map.range(..15)), vec![0, 10]);
+        assert_eq!(keys(map.range(85..)), vec![90]);
+        assert_eq!(keys(map.range((Bound::Excluded(70), Bound::Unbounded))), vec![80, 90]);
+        assert_eq!(keys(map.range((Bound::Included(40), Bound::Excluded(20)))), vec![]);
+        assert_eq!(map.range(..).next_back(), Some((&90, &9)));
+    }
+
+    #[test]
+    fn first_last_and_pop() {
+        let mut map = map();
+        assert_eq!(map.first(), Some((&0, &0)));
+        assert_eq!(map.last(), Some((&90, &9)));
+        assert_eq!(map.pop_first(), Some((0, 0)));
+        assert_eq!(map.pop_last(), Some((90, 9)));
+        assert_eq!(map.len(), 8);
+        let mut empty = Map::<u32, u32>::new();
+        assert_eq!(empty.pop_first(), None);
+        assert_eq!(empty.pop_last(), None);
+    }
+
+    #[test]
+    fn mutable_and_double_ended_iteration() {
+        let mut map = map();
+        map.iter_mut().for_each(|(k, v)| *v += k);
+        for v in map.values_mut().rev().take(2) {
+            *v = 0;
+        }
+        for (_, v) in &mut map {
+            *v += 1;
+        }
+        let values: Vec<_> = map.values().cloned().collect();
+        assert_eq!(values, vec![1, 12, 23, 34, 45, 56, 67, 78, 1, 1]);
+        let mut keys = map.keys();
+        assert_eq!(keys.len(), 10);
+        assert_eq!(keys.next_back(), Some(&90));
+        assert_eq!(keys.next(), Some(&0));
+        assert_eq!(keys.len(), 8);
+        let mut rest = map.into_iter().skip(8);
+        assert_eq!(rest.next_back(), Some((90, 1)));
+        assert_eq!(rest.next(), Some((80, 1)));
+        assert_eq!(rest.next(), None);
+        assert_eq!(rest.next(), None);
+    }
+
+    #[test]
+    fn retain_and_drain_filter() {
+        let mut map = map();
+        map.retain(|k, v| {
+            *v *= 2;
+            k % 20 == 0
+        });
+        assert_eq!(map.len(), 5);
+        let drained: Vec<_> = map.drain_filter(|k, _| *k > 40).collect();
+        assert_eq!(drained, vec![(60, 12), (80, 16)]);
+        assert_eq!(map.into_vec(), vec![(0, 0), (20, 4), (40, 8)]);
+        let mut set: Set<_> = (0..10).collect();
+        set.retain(|k| k % 3 != 0);
+        let drained: Vec<_> = set.drain_filter(|k| *k > 5).rev().collect();
+        assert_eq!(drained, vec![8, 7]);
+        assert_eq!((&set).into_iter().len(), 4);
+    }
+
+    #[test]
+    fn positional_access() {
+        let mut map = map();
+        assert_eq!(map.get_at(3), Some((&30, &3)));
+        assert_eq!(map.get_at(10), None);
+        assert_eq!(map.rank(&30), 3);
+        assert_eq!(map.rank(&35), 4);
+        assert_eq!(map.rank(&100), 10);
+        assert_eq!(map.index_of(&30), Some(3));
+        assert_eq!(map.index_of(&35), None);
+        let page: Vec<_> = map.range_at(4..7).map(|(k, _)| *k).collect();
+        assert_eq!(page, vec![40, 50, 60]);
+        if let Some((_, v)) = map.get_at_mut(0) {
+            *v = 100;
+        }
+        assert_eq!(map.remove_at(0), (0, 100));
+        assert_eq!(map.get_at(0), Some((&10, &1)));
+        let mut set: Set<_> = (0..5).collect();
+        assert_eq!(set.rank(&3), 3);
+        assert_eq!(set.remove_at(1), 1);
+        assert_eq!(set.get_at(1), Some(&2));
+        assert_eq!(set.index_of(&4), Some(3));
+    }
+
+    #[test]
+    fn standard_traits() {
+        let map = Map::from([(2, "b"), (1, "a")]);
+        assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b"}"#);
+        assert_eq!(map[&1], "a");
+        let mut other = map.clone();
+        assert_eq!(map, other);
+        other.insert(3, "c");
+        assert_ne!(map, other);
+        assert!(map < other);
+        let set = Set::from(["b", "a", "b"]);
+        assert_eq!(format!("{:?}", set), r#"{"a", "b"}"#);
+        assert_eq!(set, Set::from(["a", "b"]));
+        let mut by_set = std::collections::HashMap::new();
+        by_set.insert(set.clone(), 1);
+        assert_eq!(by_set.get(&set), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn index_missing_key() {
+        let map = Map::from([(1, 1)]);
+        let _ = map[&2];
+    }
+
+    #[test]
+    fn bounds_and_cursors() {
+        let map = map();
+        assert_eq!(map.lower_bound(&30).key(), Some(&30));
+        assert_eq!(map.upper_bound(&30).key(), Some(&40));
+        assert_eq!(map.lower_bound(&35).key(), Some(&40));
+        assert_eq!(map.upper_bound(&90).get(), None);
+        let mut cur = map.lower_bound(&0);
+        assert!(!cur.move_prev());
+        assert!(cur.move_next());
+        assert_eq!(cur.peek_prev(), Some((&0, &0)));
+        assert_eq!(cur.iter().count(), 9);
+        assert_eq!(map.floor(&35), Some((&30, &3)));
+        assert_eq!(map.floor(&30), Some((&30, &3)));
+        assert_eq!(map.floor(&5).map(|(k, _)| *k), Some(0));
+        assert_eq!(Map::<u32, u32>::new().floor(&5), None);
+        assert_eq!(map.ceiling(&35), Some((&40, &4)));
+        assert_eq!(map.ceiling(&95), None);
+    }
+}
@@ -0,0 +1,71 @@
+/// Edits counted by an edit distance
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// Insertions, deletions and substitutions
+    Levenshtein,
+    /// Levenshtein edits and transpositions of adjacent elements, with no element
+    /// edited twice (the optimal string alignment distance)
+    Damerau,
+}
+
+/// Rows of the edit distance table of a query against keys which grow by one
+/// element at a time, as on a walk down a trie or an automaton
+///
+/// A row holds the distances of a key to every prefix of the query. Entries of
+/// later rows are no smaller than the smallest entry of a row, so the walk can
+/// skip everything below a key whose row exceeds the bound
+pub struct EditRows<'q, Q> {
+    query: &'q [Q],
+    max: usize,
+    metric: Metric,
+}
+
+impl<'q, Q> EditRows<'q, Q> {
+    /// Creates the rows for keys within `max` edits of the query
+    pub fn new(query: &'q [Q], max: usize, metric: Metric) -> Self {
+        Self { query, max, metric }
+    }
+
+    /// The row of the empty key
+    pub fn first(&self) -> Vec<usize> {
+        (0..=self.query.len()).collect()
+    }
+
+    /// Computes the row of a key extended by `k` from the row of the key and the row
+    /// `prev` of the key without its `last` element, which transpositions look at
+    ///
+    /// Returns `None` if no extension of the new key is within the bound
+    pub fn next<K, F>(
+        &self,
+        prev: &[usize],
+        row: &[usize],
+        last: Option<&K>,
+        k: &K,
+        eq: F,
+    ) -> Option<Vec<usize>>
+    where F: Fn(&K, &Q) -> bool
+    {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+        for (j, q) in self.query.iter().enumerate() {
+            let substitute = row[j] + !eq(k, q) as usize;
+            let mut d = substitute.min(row[j + 1] + 1).min(next[j] + 1);
+            if let (Metric::Damerau, Some(last), 1..) = (self.metric, last, j) {
+                if eq(last, q) && eq(k, &self.query[j - 1]) {
+                    d = d.min(prev[j - 1] + 1);
+                }
+            }
+            next.push(d);
+        }
+        match next.iter().all(|d| *d > self.max) {
+            true => None,
+            false => Some(next),
+        }
+    }
+
+    /// Returns the distance of the key of the row to the query if it is within the bound
+    #[inline]
+    pub fn distance(&self, row: &[usize]) -> Option<usize> {
+        Some(row[self.query.len()]).filter(|d| *d <= self.max)
+    }
+}
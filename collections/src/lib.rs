@@ -8,6 +8,8 @@ pub mod multimap;
 pub mod slicemap;
 #[cfg(feature = "list")]
 pub mod list;
+#[cfg(feature = "edit")]
+pub mod edit;
 
 //mod dawg;
 
@@ -1,9 +1,77 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, FusedIterator};
+use core::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
 
-#[derive(Debug)]
-pub struct List<T>(Option<Rc<Node<T>>>);
+/// The shared pointer kind behind the nodes of a [PersistentList]
+pub trait PtrKind {
+    type Ptr<T>: Deref<Target = T> + Clone;
+
+    fn new<T>(value: T) -> Self::Ptr<T>;
+
+    fn try_unwrap<T>(ptr: Self::Ptr<T>) -> Result<T, Self::Ptr<T>>;
+
+    fn ptr_eq<T>(a: &Self::Ptr<T>, b: &Self::Ptr<T>) -> bool;
+}
+
+/// Nodes behind [Rc], for lists used within a thread
+#[derive(Clone, Copy, Debug)]
+pub enum RcPtr {}
+
+/// Nodes behind [Arc], for lists shared between threads
+#[derive(Clone, Copy, Debug)]
+pub enum ArcPtr {}
+
+impl PtrKind for RcPtr {
+    type Ptr<T> = Rc<T>;
+
+    #[inline]
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+
+    #[inline]
+    fn try_unwrap<T>(ptr: Rc<T>) -> Result<T, Rc<T>> {
+        Rc::try_unwrap(ptr)
+    }
 
-impl<T> List<T> {
+    #[inline]
+    fn ptr_eq<T>(a: &Rc<T>, b: &Rc<T>) -> bool {
+        Rc::ptr_eq(a, b)
+    }
+}
+
+impl PtrKind for ArcPtr {
+    type Ptr<T> = Arc<T>;
+
+    #[inline]
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+
+    #[inline]
+    fn try_unwrap<T>(ptr: Arc<T>) -> Result<T, Arc<T>> {
+        Arc::try_unwrap(ptr)
+    }
+
+    #[inline]
+    fn ptr_eq<T>(a: &Arc<T>, b: &Arc<T>) -> bool {
+        Arc::ptr_eq(a, b)
+    }
+}
+
+/// A persistent singly linked list, lists share their tails and cloning is O(1)
+pub struct PersistentList<T, P: PtrKind>(Option<P::Ptr<Node<T, P>>>);
+
+/// A persistent list for use within a thread
+pub type List<T> = PersistentList<T, RcPtr>;
+
+/// A persistent list which is [Send] and [Sync] when `T` is both
+pub type SyncList<T> = PersistentList<T, ArcPtr>;
+
+impl<T, P: PtrKind> PersistentList<T, P> {
     #[inline]
     pub fn empty() -> Self {
         Self(None)
@@ -14,18 +82,24 @@ impl<T> List<T> {
         self.0.is_none()
     }
 
+    /// Returns the length cached in the head node
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, |n| n.len)
+    }
+
     #[inline]
     pub fn head(&self) -> Option<&T> {
         self.0.as_deref().map(Node::value)
     }
 
     #[inline]
-    pub fn tail(&self) -> Option<&List<T>> {
+    pub fn tail(&self) -> Option<&Self> {
         self.0.as_deref().map(Node::tail)
     }
 
     #[inline]
-    pub fn iter(&self) -> Iter<'_, T> {
+    pub fn iter(&self) -> Iter<'_, T, P> {
         self.into()
     }
 
@@ -36,42 +110,167 @@ impl<T> List<T> {
 
     #[inline]
     pub fn put(self, item: T) -> Self {
-        Self(Some(Rc::new(Node::new(item, self))))
+        Self(Some(P::new(Node::new(item, self))))
+    }
+
+    /// Returns the list without its first `n` items, sharing the rest
+    pub fn drop(&self, n: usize) -> Self {
+        let mut list = self;
+        for _ in 0..n {
+            match list.tail() {
+                Some(tail) => list = tail,
+                None => break,
+            }
+        }
+        list.clone()
+    }
+
+    /// Returns the list of mapped items in the same order
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> PersistentList<U, P> {
+        self.iter().map(f).collect()
+    }
+
+    /// Builds the list of `items` in order in front of `tail`
+    fn prepend<I: DoubleEndedIterator<Item = T>>(items: I, tail: Self) -> Self {
+        items.rev().fold(tail, Self::put)
+    }
+}
+
+impl<T: Clone, P: PtrKind> PersistentList<T, P> {
+    pub fn reverse(&self) -> Self {
+        self.iter().fold(Self::empty(), |list, item| list.put(item.clone()))
+    }
+
+    /// Returns the items of `self` followed by `other`, copying `self` and sharing `other`
+    pub fn append(&self, other: &Self) -> Self {
+        let items: Vec<_> = self.iter().cloned().collect();
+        Self::prepend(items.into_iter(), other.clone())
+    }
+
+    /// Returns the list of items matching the predicate, sharing the longest matching tail
+    pub fn filter<F: FnMut(&T) -> bool>(&self, predicate: F) -> Self {
+        let keep: Vec<bool> = self.iter().map(predicate).collect();
+        let shared = match keep.iter().rposition(|k| !k) {
+            Some(i) => i + 1,
+            None => return self.clone(),
+        };
+        let items = self.iter().zip(keep).take(shared).filter(|(_, k)| *k);
+        let items: Vec<_> = items.map(|(item, _)| item.clone()).collect();
+        Self::prepend(items.into_iter(), self.drop(shared))
+    }
+
+    /// Returns the list of the first `n` items
+    pub fn take(&self, n: usize) -> Self {
+        if n >= self.len() {
+            return self.clone();
+        }
+        let items: Vec<_> = self.iter().take(n).cloned().collect();
+        Self::prepend(items.into_iter(), Self::empty())
+    }
+}
+
+impl<T, P: PtrKind> Clone for PersistentList<T, P> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: fmt::Debug, P: PtrKind> fmt::Debug for PersistentList<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, P: PtrKind> Default for PersistentList<T, P> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Drops the nodes in a loop, as the recursive drop of a long list overflows the stack
+impl<T, P: PtrKind> Drop for PersistentList<T, P> {
+    fn drop(&mut self) {
+        let mut next = self.0.take();
+        while let Some(node) = next {
+            next = match P::try_unwrap(node) {
+                Ok(mut node) => node.tail.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+impl<T: PartialEq, P: PtrKind> PartialEq for PersistentList<T, P> {
+    fn eq(&self, other: &Self) -> bool {
+        let same = match (&self.0, &other.0) {
+            (Some(a), Some(b)) => P::ptr_eq(a, b),
+            _ => false,
+        };
+        same || self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, P: PtrKind> Eq for PersistentList<T, P> {}
+
+impl<T: Hash, P: PtrKind> Hash for PersistentList<T, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        self.iter().for_each(|item| item.hash(state));
+    }
+}
+
+impl<T, P: PtrKind> FromIterator<T> for PersistentList<T, P> {
+    /// Collects the items into a list with the first item at the head
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<_> = iter.into_iter().collect();
+        Self::prepend(items.into_iter(), Self::empty())
     }
 }
 
-impl<T> AsRef<List<T>> for List<T> {
-    fn as_ref(&self) -> &List<T> {
+impl<'l, T, P: PtrKind> IntoIterator for &'l PersistentList<T, P> {
+    type Item = &'l T;
+    type IntoIter = Iter<'l, T, P>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, P: PtrKind> AsRef<PersistentList<T, P>> for PersistentList<T, P> {
+    fn as_ref(&self) -> &PersistentList<T, P> {
         &self
     }
 }
 
-impl<T> From<T> for List<T> {
+impl<T, P: PtrKind> From<T> for PersistentList<T, P> {
     #[inline]
     fn from(value: T) -> Self {
-        Self(Some(Rc::new(value.into())))
+        Self(Some(P::new(value.into())))
     }
 }
 
-impl<T> From<&List<T>> for List<T> {
+impl<T, P: PtrKind> From<&PersistentList<T, P>> for PersistentList<T, P> {
     #[inline]
-    fn from(list: &List<T>) -> Self {
+    fn from(list: &PersistentList<T, P>) -> Self {
         Self(list.0.clone())
     }
 }
 
-pub struct Iter<'i, T> {
-    node: Option<&'i Node<T>>,
+pub struct Iter<'i, T, P: PtrKind> {
+    node: Option<&'i Node<T, P>>,
 }
 
-impl<'l, T> From<&'l List<T>> for Iter<'l, T> {
-    fn from(list: &'l List<T>) -> Self {
+impl<'l, T, P: PtrKind> From<&'l PersistentList<T, P>> for Iter<'l, T, P> {
+    fn from(list: &'l PersistentList<T, P>) -> Self {
         let node = list.as_ref().0.as_deref();
         Self { node }
     }
 }
 
-impl<'i, T> Iterator for Iter<'i, T> {
+impl<'i, T, P: PtrKind> Iterator for Iter<'i, T, P> {
     type Item = &'i T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -83,17 +282,29 @@ impl<'i, T> Iterator for Iter<'i, T> {
             }
         }
     }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.node.map_or(0, |n| n.len);
+        (len, Some(len))
+    }
 }
 
-#[derive(Debug)]
-struct Node<T> {
+impl<T, P: PtrKind> ExactSizeIterator for Iter<'_, T, P> {}
+
+impl<T, P: PtrKind> FusedIterator for Iter<'_, T, P> {}
+
+struct Node<T, P: PtrKind> {
     value: T,
-    tail: List<T>,
+    tail: PersistentList<T, P>,
+    /// Length of the list starting at this node
+    len: usize,
 }
 
-impl<T> Node<T> {
-    fn new(value: T, tail: List<T>) -> Self {
-        Self { value, tail }
+impl<T, P: PtrKind> Node<T, P> {
+    fn new(value: T, tail: PersistentList<T, P>) -> Self {
+        let len = tail.len() + 1;
+        Self { value, tail, len }
     }
 
     #[inline]
@@ -102,19 +313,99 @@ impl<T> Node<T> {
     }
 
     #[inline]
-    fn tail(&self) -> &List<T> {
+    fn tail(&self) -> &PersistentList<T, P> {
         &self.tail
     }
 
     #[inline]
-    fn take(self) -> (T, List<T>) {
+    fn take(self) -> (T, PersistentList<T, P>) {
         (self.value, self.tail)
     }
 }
 
-impl<T> From<T> for Node<T> {
+impl<T, P: PtrKind> From<T> for Node<T, P> {
     #[inline]
     fn from(value: T) -> Self {
-        Self { value, tail: List::empty() }
+        Self { value, tail: PersistentList::empty(), len: 1 }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items(list: &List<u32>) -> Vec<u32> {
+        list.iter().cloned().collect()
+    }
+
+    fn same_node(a: &List<u32>, b: &List<u32>) -> bool {
+        match (&a.0, &b.0) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn persistent_operations() {
+        let list: List<u32> = (1..=5).collect();
+        assert_eq!(items(&list), vec![1, 2, 3, 4, 5]);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.iter().len(), 5);
+        assert_eq!(items(&list.reverse()), vec![5, 4, 3, 2, 1]);
+        assert_eq!(items(&list.take(2)), vec![1, 2]);
+        assert_eq!(items(&list.drop(3)), vec![4, 5]);
+        assert!(list.drop(9).is_empty());
+        assert_eq!(items(&list.map(|x| x * 10)), vec![10, 20, 30, 40, 50]);
+        let tail = list.drop(3);
+        let joined = list.take(1).append(&tail);
+        assert_eq!(items(&joined), vec![1, 4, 5]);
+        assert!(same_node(&joined.drop(1), &tail));
+        let odd = list.filter(|x| x % 2 == 1);
+        assert_eq!(items(&odd), vec![1, 3, 5]);
+        assert!(same_node(&odd.drop(2), &list.drop(4)));
+        assert_eq!(items(&list), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn equality_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        let hash = |list: &List<u32>| {
+            let mut h = DefaultHasher::new();
+            list.hash(&mut h);
+            h.finish()
+        };
+        let a: List<u32> = vec![1, 2, 3].into_iter().collect();
+        let b = List::empty().put(3).put(2).put(1);
+        assert_eq!(a, b);
+        assert_eq!(a, a.clone());
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, b.drop(1));
+        assert_eq!(List::<u32>::empty(), List::default());
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let list: List<u32> = (0..1_000_000).collect();
+        let shared = list.drop(500_000);
+        drop(list);
+        assert_eq!(shared.len(), 500_000);
+        assert_eq!(shared.head(), Some(&500_000));
+    }
+
+    #[test]
+    fn sync_list_between_threads() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let history: SyncList<String> = (0..100).map(|i| format!("edit {}", i)).collect();
+        assert_send_sync(&history);
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let history = history.drop(i * 10);
+                std::thread::spawn(move || history.put(format!("worker {}", i)).len())
+            })
+            .collect();
+        let lens: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(lens, vec![101, 91, 81, 71]);
+        assert_eq!(history.head().map(String::as_str), Some("edit 0"));
+        assert_eq!(history.filter(|s| s.ends_with('7')).len(), 10);
     }
 }
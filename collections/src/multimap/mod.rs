@@ -0,0 +1,600 @@
+use crate::record::Rec;
+use core::iter::FromIterator;
+use core::ops::{Bound, Range, RangeBounds};
+use core::slice;
+#[cfg(feature = "serde_derive")]
+use serde::Serialize;
+
+#[cfg(feature = "serde_derive")]
+mod serial;
+#[cfg(feature = "serde_derive")]
+pub use serial::{deserialize_sorted, grouped};
+
+/// A map with multiple values per key stored as records sorted by key
+///
+/// Deserialization sorts the records, see [deserialize_sorted] to reject unsorted input
+#[derive(Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+#[cfg_attr(feature = "serde_derive", serde(transparent))]
+pub struct MultiMap<K, V>(Vec<Rec<K, V>>);
+
+impl<K, V> Default for MultiMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> MultiMap<K, V> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&K, &V)> {
+        self.0.get(index).map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.0.iter().map(Rec::as_pair)
+    }
+
+    /// Iterates over the distinct keys in ascending order
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { records: &self.0 }
+    }
+
+    /// Iterates over the distinct keys with all their values
+    #[inline]
+    pub fn group_by_key(&self) -> GroupByKey<'_, K, V> {
+        GroupByKey { records: &self.0 }
+    }
+
+    /// Keeps only the records for which `f` returns `true`, compacting the map in one pass
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where F: FnMut(&K, &mut V) -> bool
+    {
+        self.0.retain_mut(|r| f(&r.key, &mut r.val))
+    }
+}
+
+impl<'m, K: Ord, V> MultiMap<K, V> {
+    /// Lazily iterates over the values of the key in insertion order
+    pub fn find<'k>(&'m self, k: &'k K) -> Find<'m, 'k, K, V> {
+        Find { map: self, key: k, idx: 0, phase: 0 }
+    }
+
+    pub fn find_all(&'m self, k: &K) -> FindAll<'m, K, V> {
+        let r = self.range(k).unwrap_or(0..0);
+        let records = &self.0[r];
+        let index = 0;
+        FindAll { records, index }
+    }
+
+    pub fn find_all_mut(&'m mut self, k: &K) -> FindAllMut<'m, K, V> {
+        let r = self.range(k).unwrap_or(0..0);
+        FindAllMut { records: self.0[r].iter_mut() }
+    }
+
+    /// Inserts the value after all values of the same key and returns its index
+    pub fn insert(&mut self, k: K, v: V) -> usize {
+        let i = match self.last_index(&k) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        self.0.insert(i, (k, v).into());
+        i
+    }
+
+    pub fn remove(&mut self, index: usize) -> (K, V) {
+        self.0.remove(index).into_pair()
+    }
+
+    /// Removes all values of the key and returns the number of removed values
+    pub fn remove_all(&mut self, k: &K) -> usize {
+        match self.range(k) {
+            Some(r) => self.0.drain(r).count(),
+            None => 0,
+        }
+    }
+
+    /// Removes the values of the key for which `f` returns `true`
+    /// and returns the number of removed values
+    ///
+    /// Only the records of the key are moved, the order of kept values is preserved
+    pub fn remove_where<F>(&mut self, k: &K, mut f: F) -> usize
+    where F: FnMut(&V) -> bool
+    {
+        let r = match self.range(k) {
+            Some(r) => r,
+            None => return 0,
+        };
+        let mut kept = r.start;
+        for i in r.clone() {
+            if !f(&self.0[i].val) {
+                self.0.swap(kept, i);
+                kept += 1;
+            }
+        }
+        self.0.drain(kept..r.end).count()
+    }
+
+    #[inline]
+    pub fn contains(&self, k: &K) -> bool {
+        self.first_index(k).is_ok()
+    }
+
+    /// Returns the number of values of the key
+    #[inline]
+    pub fn count(&self, k: &K) -> usize {
+        self.range(k).map_or(0, |r| r.len())
+    }
+
+    /// Returns the index of the first record of the key or the index
+    /// where the key could be inserted before all greater keys
+    fn first_index(&self, k: &K) -> Result<usize, usize> {
+        let i = self.0.partition_point(|r| r.key < *k);
+        match self.0.get(i) {
+            Some(r) if r.key == *k => Ok(i),
+            _ => Err(i),
+        }
+    }
+
+    /// Returns the index of the last record of the key or the index
+    /// where the key could be inserted after all lesser keys
+    fn last_index(&self, k: &K) -> Result<usize, usize> {
+        let i = self.0.partition_point(|r| r.key <= *k);
+        match i.checked_sub(1).map(|j| &self.0[j]) {
+            Some(r) if r.key == *k => Ok(i - 1),
+            _ => Err(i),
+        }
+    }
+
+    fn range(&self, k: &K) -> Option<Range<usize>> {
+        let first = self.first_index(k).ok()?;
+        let len = self.0[first..].partition_point(|r| r.key == *k);
+        Some(first..first + len)
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for MultiMap<K, V> {
+    /// Collects the pairs and sorts them once, values of the same key keep the input order
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for MultiMap<K, V> {
+    /// Appends the pairs and restores the order with a single stable sort,
+    /// so new values of a key follow its existing values
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let len = self.0.len();
+        self.0.extend(iter.into_iter().map(Rec::from));
+        if self.0.len() > len {
+            self.0.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+    }
+}
+
+/// A multimap which keeps values of the same key sorted, so it is
+/// an ordered set of `(K, V)` pairs with fast per-key range queries
+///
+/// Equal pairs are kept unless the map is created with [SortedMultiMap::unique].
+/// Deserialization sorts the pairs and drops the repeated ones of a unique map
+#[derive(Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+pub struct SortedMultiMap<K, V> {
+    records: Vec<Rec<K, V>>,
+    unique: bool,
+}
+
+impl<K, V> Default for SortedMultiMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> SortedMultiMap<K, V> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { records: Vec::new(), unique: false }
+    }
+
+    /// Creates a map which stores every `(K, V)` pair at most once
+    #[inline]
+    pub const fn unique() -> Self {
+        Self { records: Vec::new(), unique: true }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.records.clear()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&K, &V)> {
+        self.records.get(index).map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.records.iter().map(Rec::as_pair)
+    }
+
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { records: &self.records }
+    }
+
+    #[inline]
+    pub fn group_by_key(&self) -> GroupByKey<'_, K, V> {
+        GroupByKey { records: &self.records }
+    }
+}
+
+impl<K: Ord, V: Ord> SortedMultiMap<K, V> {
+    /// Inserts the pair keeping values of the key sorted, returns `false`
+    /// if the map is unique and already contains the pair
+    pub fn insert(&mut self, k: K, v: V) -> bool {
+        let i = match self.search(&k, &v) {
+            Ok(_) if self.unique => return false,
+            Ok(i) => i + self.records[i..].partition_point(|r| r.key == k && r.val == v),
+            Err(i) => i,
+        };
+        self.records.insert(i, (k, v).into());
+        true
+    }
+
+    #[inline]
+    pub fn contains(&self, k: &K, v: &V) -> bool {
+        self.search(k, v).is_ok()
+    }
+
+    /// Removes one occurrence of the pair
+    pub fn remove(&mut self, k: &K, v: &V) -> Option<(K, V)> {
+        let i = self.search(k, v).ok()?;
+        Some(self.records.remove(i).into_pair())
+    }
+
+    /// Removes all values of the key and returns the number of removed values
+    pub fn remove_all(&mut self, k: &K) -> usize {
+        let r = self.value_range(k, ..);
+        self.records.drain(r).count()
+    }
+
+    /// Iterates over the values of the key in ascending order
+    #[inline]
+    pub fn find_all(&self, k: &K) -> FindAll<'_, K, V> {
+        self.range_for(k, ..)
+    }
+
+    /// Iterates over the values of the key within the range in ascending order
+    #[inline]
+    pub fn range_for<R: RangeBounds<V>>(&self, k: &K, range: R) -> FindAll<'_, K, V> {
+        let records = &self.records[self.value_range(k, range)];
+        FindAll { records, index: 0 }
+    }
+
+    #[inline]
+    pub fn count(&self, k: &K) -> usize {
+        self.find_all(k).len()
+    }
+
+    #[inline]
+    pub fn first_for(&self, k: &K) -> Option<&V> {
+        self.find_all(k).next()
+    }
+
+    #[inline]
+    pub fn last_for(&self, k: &K) -> Option<&V> {
+        self.find_all(k).last()
+    }
+
+    fn search(&self, k: &K, v: &V) -> Result<usize, usize> {
+        self.records.binary_search_by(|r| r.key.cmp(k).then_with(|| r.val.cmp(v)))
+    }
+
+    fn value_range<R: RangeBounds<V>>(&self, k: &K, range: R) -> Range<usize> {
+        let start = self.records.partition_point(|r| {
+            r.key < *k
+                || r.key == *k
+                    && match range.start_bound() {
+                        Bound::Included(s) => r.val < *s,
+                        Bound::Excluded(s) => r.val <= *s,
+                        Bound::Unbounded => false,
+                    }
+        });
+        let end = self.records.partition_point(|r| {
+            r.key < *k
+                || r.key == *k
+                    && match range.end_bound() {
+                        Bound::Included(e) => r.val <= *e,
+                        Bound::Excluded(e) => r.val < *e,
+                        Bound::Unbounded => true,
+                    }
+        });
+        start..end.max(start)
+    }
+}
+
+pub struct Find<'m, 'k, K, V> {
+    map: &'m MultiMap<K, V>,
+    key: &'k K,
+    idx: usize,
+    phase: u8,
+}
+
+impl<'m, 'k, K: Ord, V> Iterator for Find<'m, 'k, K, V> {
+    type Item = &'m V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.phase {
+            0 => {
+                self.idx = self.map.first_index(self.key).ok()?;
+                self.phase = 1;
+                self.next()
+            }
+            1 => {
+                let r = self.map.0.get(self.idx)?;
+                if self.key == r.key() {
+                    self.idx += 1;
+                    Some(r.value())
+                } else {
+                    self.phase = 2;
+                    None
+                }
+            }
+            2 => None,
+            _ => unreachable!()
+        }
+    }
+}
+
+pub struct FindAll<'m, K, V> {
+    records: &'m [Rec<K, V>],
+    index: usize,
+}
+
+impl<K, V> FindAll<'_, K, V> {
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+}
+
+impl<'m, K, V> Iterator for FindAll<'m, K, V> {
+    type Item = &'m V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.records.len() {
+            return None;
+        }
+        let value = self.records[self.index].value();
+        self.index += 1;
+        Some(value)
+    }
+}
+
+pub struct FindAllMut<'m, K, V> {
+    records: slice::IterMut<'m, Rec<K, V>>,
+}
+
+impl<K, V> FindAllMut<'_, K, V> {
+    pub fn is_empty(&self) -> bool {
+        self.records.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+}
+
+impl<'m, K, V> Iterator for FindAllMut<'m, K, V> {
+    type Item = &'m mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next().map(Rec::value_mut)
+    }
+}
+
+pub struct GroupByKey<'m, K, V> {
+    records: &'m [Rec<K, V>],
+}
+
+impl<'m, K: Ord, V> Iterator for GroupByKey<'m, K, V> {
+    type Item = (&'m K, FindAll<'m, K, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let k = self.records.first()?.key();
+        let len = self.records.partition_point(|r| r.key == *k);
+        let (records, rest) = self.records.split_at(len);
+        self.records = rest;
+        Some((k, FindAll { records, index: 0 }))
+    }
+}
+
+pub struct Keys<'m, K, V> {
+    records: &'m [Rec<K, V>],
+}
+
+impl<'m, K: Ord, V> Iterator for Keys<'m, K, V> {
+    type Item = &'m K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let k = self.records.first()?.key();
+        let len = self.records.partition_point(|r| r.key == *k);
+        self.records = &self.records[len..];
+        Some(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use random::Source;
+    use std::collections::BTreeMap;
+
+    fn check(map: &MultiMap<u64, u64>, model: &BTreeMap<u64, Vec<u64>>) {
+        assert_eq!(map.len(), model.values().map(Vec::len).sum::<usize>());
+        assert!(map.keys().eq(model.keys()));
+        for k in 0..20 {
+            let expected = model.get(&k).cloned().unwrap_or_default();
+            assert_eq!(map.find(&k).cloned().collect::<Vec<_>>(), expected);
+            assert_eq!(map.find_all(&k).cloned().collect::<Vec<_>>(), expected);
+            assert_eq!(map.count(&k), expected.len());
+            assert_eq!(map.contains(&k), !expected.is_empty());
+        }
+    }
+
+    #[test]
+    fn insert_keeps_order_of_equal_keys() {
+        let mut map = MultiMap::new();
+        assert_eq!(map.insert(2, 'a'), 0);
+        assert_eq!(map.insert(1, 'b'), 0);
+        assert_eq!(map.insert(2, 'c'), 2);
+        assert_eq!(map.insert(3, 'd'), 3);
+        assert_eq!(map.insert(2, 'e'), 3);
+        assert_eq!(map.find(&2).collect::<Vec<_>>(), vec![&'a', &'c', &'e']);
+        assert_eq!(map.find_all(&2).len(), 3);
+        assert_eq!(map.find(&4).next(), None);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        assert_eq!(map.remove_all(&2), 3);
+        assert_eq!(map.remove_all(&2), 0);
+        assert_eq!(map.remove(1), (3, 'd'));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn groups_and_value_mutation() {
+        let mut map = MultiMap::new();
+        for (k, v) in [(2, 20), (1, 10), (2, 21), (3, 30), (2, 22), (1, 11)] {
+            map.insert(k, v);
+        }
+        let groups: Vec<(_, Vec<_>)> =
+            map.group_by_key().map(|(k, vs)| (*k, vs.cloned().collect())).collect();
+        assert_eq!(groups, vec![(1, vec![10, 11]), (2, vec![20, 21, 22]), (3, vec![30])]);
+        map.find_all_mut(&2).for_each(|v| *v += 100);
+        assert_eq!(map.remove_where(&2, |v| *v == 121), 1);
+        assert_eq!(map.remove_where(&4, |_| true), 0);
+        assert_eq!(map.find(&2).collect::<Vec<_>>(), vec![&120, &122]);
+        map.retain(|k, v| {
+            *v += 1;
+            *k != 1
+        });
+        let rest: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(rest, vec![(2, 121), (2, 123), (3, 31)]);
+    }
+
+    #[test]
+    fn sorted_values_within_key() {
+        let mut map = SortedMultiMap::new();
+        for (k, v) in [(1, 30), (2, 5), (1, 10), (1, 20), (1, 10)] {
+            assert!(map.insert(k, v));
+        }
+        assert_eq!(map.find_all(&1).collect::<Vec<_>>(), vec![&10, &10, &20, &30]);
+        assert!(map.contains(&1, &20));
+        assert!(!map.contains(&2, &20));
+        assert_eq!(map.range_for(&1, 11..=30).collect::<Vec<_>>(), vec![&20, &30]);
+        assert_eq!(map.range_for(&1, ..20).count(), 2);
+        assert_eq!(map.range_for(&1, (Bound::Excluded(10), Bound::Unbounded)).count(), 2);
+        assert_eq!(map.range_for(&3, ..).count(), 0);
+        assert_eq!(map.first_for(&1), Some(&10));
+        assert_eq!(map.last_for(&1), Some(&30));
+        assert_eq!(map.remove(&1, &10), Some((1, 10)));
+        assert_eq!(map.count(&1), 3);
+        assert_eq!(map.remove_all(&1), 3);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&2, &5)]);
+    }
+
+    #[test]
+    fn unique_sorted_values() {
+        let mut map = SortedMultiMap::unique();
+        assert!(map.insert("user", 3));
+        assert!(map.insert("user", 1));
+        assert!(!map.insert("user", 3));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![&"user"]);
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut map: MultiMap<_, _> = vec![(2, 'a'), (1, 'b'), (2, 'c')].into_iter().collect();
+        map.extend(vec![(2, 'd'), (0, 'e')]);
+        let pairs: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(pairs, vec![(0, 'e'), (1, 'b'), (2, 'a'), (2, 'c'), (2, 'd')]);
+    }
+
+    #[test]
+    fn random_ops_against_model() {
+        let mut source = random::default(42);
+        let mut next = |bound: u64| source.read_u64() % bound;
+        for _ in 0..20 {
+            let mut map = MultiMap::new();
+            let mut model = BTreeMap::<u64, Vec<u64>>::new();
+            for _ in 0..300 {
+                let k = next(20);
+                match next(10) {
+                    0 => {
+                        let n = model.remove(&k).map_or(0, |v| v.len());
+                        assert_eq!(map.remove_all(&k), n);
+                    }
+                    1 => {
+                        let odd = |v: &u64| v % 2 == 1;
+                        let values = model.entry(k).or_default();
+                        let n = values.len();
+                        values.retain(|v| !odd(v));
+                        assert_eq!(map.remove_where(&k, odd), n - values.len());
+                        if values.is_empty() {
+                            model.remove(&k);
+                        }
+                    }
+                    _ => {
+                        let v = next(1000);
+                        let i = map.insert(k, v);
+                        model.entry(k).or_default().push(v);
+                        assert_eq!(map.get(i), Some((&k, &v)));
+                    }
+                }
+            }
+            check(&map, &model);
+            let groups: Vec<_> =
+                map.group_by_key().map(|(k, vs)| (*k, vs.cloned().collect())).collect();
+            assert_eq!(groups, model.clone().into_iter().collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use super::{MultiMap, SortedMultiMap};
+use crate::record::Rec;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{Error, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// Deserializes the flat list of records and sorts them by key
+///
+/// The sort is stable, so values of the same key keep the order of the input
+impl<'de, K, V> Deserialize<'de> for MultiMap<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let mut records = Vec::<Rec<K, V>>::deserialize(d)?;
+        records.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(MultiMap(records))
+    }
+}
+
+/// The serialized form of [SortedMultiMap]
+#[derive(Deserialize)]
+#[serde(rename = "SortedMultiMap")]
+struct SortedRecords<K, V> {
+    records: Vec<Rec<K, V>>,
+    unique: bool,
+}
+
+/// Deserializes the records and sorts them by key and value,
+/// repeated pairs are dropped if the map is unique
+impl<'de, K, V> Deserialize<'de> for SortedMultiMap<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de> + Ord,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let SortedRecords { mut records, unique } = SortedRecords::<K, V>::deserialize(d)?;
+        records.sort_by(|a, b| a.key.cmp(&b.key).then_with(|| a.val.cmp(&b.val)));
+        if unique {
+            records.dedup_by(|a, b| a.key == b.key && a.val == b.val);
+        }
+        Ok(SortedMultiMap { records, unique })
+    }
+}
+
+/// Deserializes the flat list of records and rejects input not sorted by key
+///
+/// Use it with `#[serde(deserialize_with = "multimap::deserialize_sorted")]`
+/// when the input must be kept exactly as it was written
+pub fn deserialize_sorted<'de, D, K, V>(d: D) -> Result<MultiMap<K, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    let records = Vec::<Rec<K, V>>::deserialize(d)?;
+    match records.windows(2).position(|w| w[0].key > w[1].key) {
+        Some(i) => Err(D::Error::custom(format_args!("multimap record {} is out of order", i + 1))),
+        None => Ok(MultiMap(records)),
+    }
+}
+
+/// Represents a [MultiMap] as a map of lists `{"k": [v1, v2]}`
+///
+/// Use it with `#[serde(with = "multimap::grouped")]`
+pub mod grouped {
+    use super::*;
+    use serde::ser::SerializeMap;
+    use serde::{Serialize, Serializer};
+
+    struct Values<'m, K, V>(&'m [Rec<K, V>]);
+
+    impl<K, V: Serialize> Serialize for Values<'_, K, V> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_seq(self.0.iter().map(Rec::value))
+        }
+    }
+
+    pub fn serialize<S, K, V>(map: &MultiMap<K, V>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Serialize + PartialEq,
+        V: Serialize,
+    {
+        let mut out = s.serialize_map(None)?;
+        let mut records = &map.0[..];
+        while let Some(first) = records.first() {
+            let len = records.partition_point(|r| r.key == first.key);
+            let (group, rest) = records.split_at(len);
+            out.serialize_entry(&first.key, &Values(group))?;
+            records = rest;
+        }
+        out.end()
+    }
+
+    pub fn deserialize<'de, D, K, V>(d: D) -> Result<MultiMap<K, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de> + Ord + Clone,
+        V: Deserialize<'de>,
+    {
+        d.deserialize_map(GroupedVisitor(PhantomData))
+    }
+
+    struct GroupedVisitor<K, V>(PhantomData<MultiMap<K, V>>);
+
+    impl<'de, K, V> Visitor<'de> for GroupedVisitor<K, V>
+    where
+        K: Deserialize<'de> + Ord + Clone,
+        V: Deserialize<'de>,
+    {
+        type Value = MultiMap<K, V>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map of keys to lists of values")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut records = Vec::with_capacity(access.size_hint().unwrap_or(0));
+            while let Some((k, values)) = access.next_entry::<K, Vec<V>>()? {
+                records.extend(values.into_iter().map(|v| Rec::from((k.clone(), v))));
+            }
+            records.sort_by(|a, b| a.key.cmp(&b.key));
+            Ok(MultiMap(records))
+        }
+    }
+}
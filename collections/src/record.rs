@@ -1,7 +1,7 @@
 #[cfg(feature = "serde_derive")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub(crate) struct Rec<K, V> {
     pub(crate) key: K,
@@ -31,6 +31,10 @@ impl<K, V> Rec<K, V> {
         (&self.key, &self.val)
     }
 
+    pub fn as_pair_mut(&mut self) -> (&K, &mut V) {
+        (&self.key, &mut self.val)
+    }
+
     pub fn into_pair(self) -> (K, V) {
         (self.key, self.val)
     }
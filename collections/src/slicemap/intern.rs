@@ -0,0 +1,186 @@
+use super::StringMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Marks an empty slot of the hash index
+const EMPTY: u32 = u32::MAX;
+
+/// Maps strings to dense `u32` symbols in the order they were interned
+///
+/// Strings are kept in one [StringMap] buffer in symbol order, lookups go
+/// through an open addressing hash index of symbols next to it
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: StringMap<u32>,
+    index: Vec<u32>,
+    hasher: RandomState,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an interner for `len` strings with `bytes` bytes in total
+    pub fn with_capacity(len: usize, bytes: usize) -> Self {
+        let mut interner = Self { strings: StringMap::with_capacity(bytes), ..Self::default() };
+        interner.strings.0.map.reserve(len);
+        interner.resize((len * 2).next_power_of_two());
+        interner
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings.len() == 0
+    }
+
+    /// Returns the symbol of the string, interning it if it is new
+    pub fn intern<S: AsRef<str>>(&mut self, s: S) -> u32 {
+        let s = s.as_ref();
+        if let Some(sym) = self.get(s) {
+            return sym;
+        }
+        if (self.len() + 1) * 2 > self.index.len() {
+            self.resize((self.index.len() * 2).max(16));
+        }
+        let sym = self.len() as u32;
+        assert!(sym != EMPTY, "interner is full");
+        self.strings.0.put(s.bytes(), sym);
+        let slot = self.slot(s).unwrap_err();
+        self.index[slot] = sym;
+        sym
+    }
+
+    /// Returns the symbol of an interned string
+    #[inline]
+    pub fn get<S: AsRef<str>>(&self, s: S) -> Option<u32> {
+        let slot = self.slot(s.as_ref()).ok()?;
+        Some(self.index[slot])
+    }
+
+    #[inline]
+    pub fn resolve(&self, sym: u32) -> Option<&str> {
+        self.strings.key(sym as usize)
+    }
+
+    /// Iterates over the strings in symbol order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.strings.iter().map(|(s, sym)| (*sym, s))
+    }
+
+    /// Sorts the strings into a read-only interner with the same symbols
+    pub fn freeze(self) -> FrozenInterner {
+        let mut strings = self.strings;
+        strings.sort();
+        let mut positions = vec![0; strings.len()];
+        for (i, (_, sym)) in strings.iter().enumerate() {
+            positions[*sym as usize] = i as u32;
+        }
+        FrozenInterner { strings, positions }
+    }
+
+    /// Finds the slot of the string or the empty slot where its symbol belongs
+    fn slot(&self, s: &str) -> Result<usize, usize> {
+        if self.index.is_empty() {
+            return Err(0);
+        }
+        let mask = self.index.len() - 1;
+        let mut i = self.hasher.hash_one(s) as usize & mask;
+        loop {
+            match self.index[i] {
+                EMPTY => return Err(i),
+                sym if self.resolve(sym) == Some(s) => return Ok(i),
+                _ => i = (i + 1) & mask,
+            }
+        }
+    }
+
+    fn resize(&mut self, len: usize) {
+        self.index = vec![EMPTY; len];
+        for sym in 0..self.len() as u32 {
+            let s = self.strings.key(sym as usize).unwrap();
+            let slot = self.slot(s).unwrap_err();
+            self.index[slot] = sym;
+        }
+    }
+}
+
+/// A read-only [Interner] with strings sorted in a [StringMap],
+/// lookups use binary search and need no hash index
+#[derive(Debug, Default)]
+pub struct FrozenInterner {
+    strings: StringMap<u32>,
+    /// Positions of the strings in the map by symbol
+    positions: Vec<u32>,
+}
+
+impl FrozenInterner {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings.len() == 0
+    }
+
+    #[inline]
+    pub fn get<S: AsRef<str>>(&self, s: S) -> Option<u32> {
+        self.strings.binary_search(s).copied()
+    }
+
+    #[inline]
+    pub fn resolve(&self, sym: u32) -> Option<&str> {
+        let i = *self.positions.get(sym as usize)?;
+        self.strings.key(i as usize)
+    }
+
+    /// Returns the strings sorted in a [StringMap] with their symbols as values
+    #[inline]
+    pub fn strings(&self) -> &StringMap<u32> {
+        &self.strings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intern_and_resolve() {
+        let mut interner = Interner::new();
+        let words = ["let", "x", "=", "y", "+", "x", "let", ""];
+        let syms: Vec<_> = words.iter().map(|w| interner.intern(w)).collect();
+        assert_eq!(syms, vec![0, 1, 2, 3, 4, 1, 0, 5]);
+        assert_eq!(interner.len(), 6);
+        assert_eq!(interner.get("y"), Some(3));
+        assert_eq!(interner.get("z"), None);
+        assert_eq!(interner.resolve(4), Some("+"));
+        assert_eq!(interner.resolve(6), None);
+        let strings: Vec<_> = interner.iter().map(|(_, s)| s).collect();
+        assert_eq!(strings, vec!["let", "x", "=", "y", "+", ""]);
+    }
+
+    #[test]
+    fn freeze_keeps_symbols() {
+        let mut interner = Interner::with_capacity(4, 16);
+        let words: Vec<String> = (0..1000).map(|i| format!("id{}", (i * 7919) % 1000)).collect();
+        let syms: Vec<_> = words.iter().map(|w| interner.intern(w)).collect();
+        assert!(syms.iter().enumerate().all(|(i, sym)| *sym == i as u32));
+        let frozen = interner.freeze();
+        assert_eq!(frozen.len(), 1000);
+        assert!(frozen.strings().is_sorted());
+        for (w, sym) in words.iter().zip(syms) {
+            assert_eq!(frozen.get(w), Some(sym));
+            assert_eq!(frozen.resolve(sym), Some(w.as_str()));
+        }
+        assert_eq!(frozen.get("id1000"), None);
+        assert_eq!(frozen.resolve(1000), None);
+    }
+}
@@ -0,0 +1,413 @@
+use super::{Iter, SliceMap};
+use core::fmt;
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+use core::ops::RangeBounds;
+use std::ffi::OsStr;
+use std::path::Path;
+#[cfg(feature = "serde_derive")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+/// A key type of [KeyedMap] stored as bytes and ordered by them
+///
+/// # Safety
+///
+/// [ByteKey::from_key_bytes] must be sound for the bytes of any key and
+/// for any bytes accepted by [ByteKey::is_valid]
+pub unsafe trait ByteKey {
+    fn as_key_bytes(&self) -> &[u8];
+
+    /// Checks that the bytes of a deserialized map are a valid key
+    fn is_valid(bytes: &[u8]) -> bool;
+
+    /// # Safety
+    ///
+    /// The bytes must be the bytes of a key or be accepted by [ByteKey::is_valid]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self;
+}
+
+unsafe impl ByteKey for [u8] {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self
+    }
+
+    #[inline]
+    fn is_valid(_: &[u8]) -> bool {
+        true
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        bytes
+    }
+}
+
+unsafe impl ByteKey for str {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    #[inline]
+    fn is_valid(bytes: &[u8]) -> bool {
+        core::str::from_utf8(bytes).is_ok()
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        core::str::from_utf8_unchecked(bytes)
+    }
+}
+
+/// Stored as [OsStr::as_encoded_bytes], so a serialized map can only be read on the
+/// platform family it was written on, and only UTF-8 keys are accepted off Unix
+unsafe impl ByteKey for OsStr {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self.as_encoded_bytes()
+    }
+
+    #[inline]
+    fn is_valid(bytes: &[u8]) -> bool {
+        cfg!(unix) || core::str::from_utf8(bytes).is_ok()
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        OsStr::from_encoded_bytes_unchecked(bytes)
+    }
+}
+
+unsafe impl ByteKey for Path {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self.as_os_str().as_key_bytes()
+    }
+
+    #[inline]
+    fn is_valid(bytes: &[u8]) -> bool {
+        OsStr::is_valid(bytes)
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        Path::new(OsStr::from_key_bytes(bytes))
+    }
+}
+
+/// A sorted map with keys of the unsized type `Q` stored in one byte buffer
+///
+/// Keys are ordered by their bytes, so [PathMap] orders paths by bytes rather than components
+#[cfg_attr(feature = "serde_derive", derive(Serialize), serde(transparent))]
+pub struct KeyedMap<Q: ?Sized, V>(
+    pub(super) SliceMap<u8, V>,
+    #[cfg_attr(feature = "serde_derive", serde(skip))] PhantomData<Q>,
+);
+
+pub type StringMap<V> = KeyedMap<str, V>;
+/// A map with arbitrary binary keys
+pub type BytesMap<V> = KeyedMap<[u8], V>;
+/// A map with platform strings which are not guaranteed to be UTF-8
+pub type OsStrMap<V> = KeyedMap<OsStr, V>;
+/// A map with filesystem paths which are not guaranteed to be UTF-8
+pub type PathMap<V> = KeyedMap<Path, V>;
+
+/// A key occurring twice in the input of [KeyedMap::try_from_iter]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKeyError<K> {
+    pub key: K,
+    /// Position of the first occurrence in the input
+    pub first: usize,
+    /// Position of the second occurrence in the input
+    pub second: usize,
+}
+
+impl<K: fmt::Debug> fmt::Display for DuplicateKeyError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "duplicate key {:?} at {} and {}", self.key, self.first, self.second)
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for DuplicateKeyError<K> {}
+
+impl<Q: ?Sized, V> Default for KeyedMap<Q, V> {
+    #[inline]
+    fn default() -> Self {
+        Self(SliceMap::new(), PhantomData)
+    }
+}
+
+impl<Q: ByteKey + fmt::Debug + ?Sized, V: fmt::Debug> fmt::Debug for KeyedMap<Q, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<Q: ByteKey + ?Sized, V> KeyedMap<Q, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self(SliceMap::with_capacity(cap), PhantomData)
+    }
+
+    /// Builds the map from records with unique keys, fails on the first duplicate key
+    pub fn try_from_iter<S, I>(iter: I) -> Result<Self, DuplicateKeyError<S>>
+    where
+        S: AsRef<Q>,
+        I: IntoIterator<Item = (S, V)>,
+    {
+        let iter = iter.into_iter().map(|(k, v)| (KeyBytes(k, PhantomData), v));
+        match SliceMap::try_from_iter(iter) {
+            Ok(map) => Ok(Self(map, PhantomData)),
+            Err(e) => Err(DuplicateKeyError { key: e.key.0, first: e.first, second: e.second }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    /// Inserts the record keeping keys sorted, replaces the old value
+    /// if the key is already present
+    #[inline]
+    pub fn put<S: AsRef<Q>>(&mut self, s: S, v: V) {
+        self.insert(s, v);
+    }
+
+    /// Inserts the record keeping keys sorted, replaces and returns
+    /// the old value if the key is already present
+    #[inline]
+    pub fn insert<S: AsRef<Q>>(&mut self, s: S, v: V) -> Option<V> {
+        self.0.insert(s.as_ref().as_key_bytes(), v)
+    }
+
+    #[inline]
+    pub fn contains_key<S: AsRef<Q>>(&self, s: S) -> bool {
+        self.0.contains_key(s.as_ref().as_key_bytes())
+    }
+
+    #[inline]
+    pub fn remove<S: AsRef<Q>>(&mut self, s: S) -> Option<V> {
+        self.0.remove(s.as_ref().as_key_bytes())
+    }
+
+    #[inline]
+    pub fn is_sorted(&self) -> bool {
+        self.0.is_sorted()
+    }
+
+    /// Restores the order of keys after deserializing unsorted input
+    #[inline]
+    pub fn sort(&mut self) {
+        self.0.sort()
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&Q, &V)> {
+        let (bytes, v) = self.0.get(index)?;
+        Some((to_key(bytes), v))
+    }
+
+    pub fn key(&self, index: usize) -> Option<&Q> {
+        self.0.key(index).map(to_key)
+    }
+
+    pub fn value(&self, index: usize) -> Option<&V> {
+        self.0.value(index)
+    }
+
+    pub fn binary_search<S: AsRef<Q>>(&self, s: S) -> Option<&V> {
+        self.0.get_by_key(s.as_ref().as_key_bytes())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> KeyedIter<'_, Q, V> {
+        KeyedIter(self.0.iter(), PhantomData)
+    }
+
+    /// Iterates over the records with keys within the range, e.g. `"a".."b"`
+    #[inline]
+    pub fn range<'r, R: RangeBounds<&'r Q>>(&self, range: R) -> KeyedIter<'_, Q, V>
+    where Q: 'r
+    {
+        let start = range.start_bound().map(|s| s.as_key_bytes());
+        let end = range.end_bound().map(|s| s.as_key_bytes());
+        KeyedIter(self.0.range((start, end)), PhantomData)
+    }
+
+    /// Iterates over the records with keys starting with the prefix
+    #[inline]
+    pub fn prefix<S: AsRef<Q>>(&self, s: S) -> KeyedIter<'_, Q, V> {
+        KeyedIter(self.0.prefix(s.as_ref().as_key_bytes()), PhantomData)
+    }
+
+    /// Finds the record with the longest key which is a prefix of `s`
+    pub fn longest_prefix_of<S: AsRef<Q>>(&self, s: S) -> Option<(&Q, &V)> {
+        let (bytes, v) = self.0.longest_prefix_of(s.as_ref().as_key_bytes())?;
+        Some((to_key(bytes), v))
+    }
+}
+
+/// Converts the bytes of a stored key back to the key
+///
+/// Keys are only added from `Q` values or from deserialized maps which were
+/// checked with [ByteKey::is_valid], which is what `from_key_bytes` requires
+#[inline]
+fn to_key<Q: ByteKey + ?Sized>(bytes: &[u8]) -> &Q {
+    unsafe { Q::from_key_bytes(bytes) }
+}
+
+/// Views a key as bytes for the byte-keyed [SliceMap]
+struct KeyBytes<S, Q: ?Sized>(S, PhantomData<Q>);
+
+impl<Q: ByteKey + ?Sized, S: AsRef<Q>> AsRef<[u8]> for KeyBytes<S, Q> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref().as_key_bytes()
+    }
+}
+
+impl<Q: ByteKey + ?Sized, V, S: AsRef<Q>> FromIterator<(S, V)> for KeyedMap<Q, V> {
+    /// Collects the records with a single sort, only the last value of duplicate keys is kept
+    fn from_iter<I: IntoIterator<Item = (S, V)>>(iter: I) -> Self {
+        let mut map = SliceMap::new();
+        for (k, v) in iter {
+            map.put(k.as_ref().as_key_bytes().iter().cloned(), v);
+        }
+        map.sort();
+        Self(map, PhantomData)
+    }
+}
+
+impl<Q: ByteKey + ?Sized, V, S: AsRef<Q>> From<Vec<(S, V)>> for KeyedMap<Q, V> {
+    /// Panics on duplicate keys, use [KeyedMap::try_from_iter] to handle them
+    /// or [FromIterator] to keep the last value
+    fn from(src: Vec<(S, V)>) -> Self {
+        match Self::try_from_iter(src) {
+            Ok(map) => map,
+            Err(e) => panic!("duplicate slice map keys at {} and {}", e.first, e.second),
+        }
+    }
+}
+
+/// Rejects maps with keys which are out of the buffer, invalid for `Q`,
+/// unsorted or duplicate, as lookups rely on strictly ascending keys
+#[cfg(feature = "serde_derive")]
+impl<'de, Q, V> Deserialize<'de> for KeyedMap<Q, V>
+where
+    Q: ByteKey + ?Sized,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let map = SliceMap::<u8, V>::deserialize(d)?;
+        if let Some(i) = (0..map.len()).find(|i| !Q::is_valid(&map.buf[map.key_range(*i)])) {
+            return Err(D::Error::custom(format_args!("slice map key {} is not valid", i)));
+        }
+        let key = |i| &map.buf[map.key_range(i)];
+        match (1..map.len()).find(|i| key(i - 1) >= key(*i)) {
+            Some(i) if key(i - 1) == key(i) => {
+                Err(D::Error::custom(format_args!("slice map key {} is a duplicate", i)))
+            }
+            Some(i) => Err(D::Error::custom(format_args!("slice map key {} is out of order", i))),
+            None => Ok(Self(map, PhantomData)),
+        }
+    }
+}
+
+/// An iterator over a range of records of a [KeyedMap] in ascending key order
+pub struct KeyedIter<'m, Q: ?Sized, V>(Iter<'m, u8, V>, PhantomData<&'m Q>);
+
+pub type StrIter<'m, V> = KeyedIter<'m, str, V>;
+
+impl<'m, Q: ByteKey + ?Sized, V> Iterator for KeyedIter<'m, Q, V> {
+    type Item = (&'m Q, &'m V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (to_key(k), v))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<Q: ByteKey + ?Sized, V> DoubleEndedIterator for KeyedIter<'_, Q, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (to_key(k), v))
+    }
+}
+
+impl<Q: ByteKey + ?Sized, V> ExactSizeIterator for KeyedIter<'_, Q, V> {}
+
+impl<Q: ByteKey + ?Sized, V> FusedIterator for KeyedIter<'_, Q, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn duplicate_keys() {
+        let src = vec![("b", 1), ("a", 2), ("c", 3), ("a", 4)];
+        let err = StringMap::try_from_iter(src.clone()).err().unwrap();
+        assert_eq!(err, DuplicateKeyError { key: "a", first: 1, second: 3 });
+        assert_eq!(err.to_string(), r#"duplicate key "a" at 1 and 3"#);
+        let map: StringMap<_> = src.into_iter().collect();
+        assert_eq!(map.binary_search("a"), Some(&4));
+        assert_eq!(map.len(), 3);
+        let unique = StringMap::try_from_iter(vec![("b", 1), ("a", 2)]).unwrap();
+        assert_eq!(format!("{:?}", unique), r#"{"a": 2, "b": 1}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate slice map keys at 0 and 1")]
+    fn from_vec_panics_on_duplicate_keys() {
+        let _: StringMap<_> = vec![("a", 1), ("a", 2)].into();
+    }
+
+    #[test]
+    fn binary_keys() {
+        let mut map = BytesMap::new();
+        map.put(&[0xff, 0x00][..], 1);
+        map.put(&[0x00][..], 2);
+        map.put(vec![0xff], 3);
+        let keys: Vec<&[u8]> = map.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![&[0x00][..], &[0xff], &[0xff, 0x00]]);
+        assert_eq!(map.prefix([0xff]).len(), 2);
+        assert_eq!(map.longest_prefix_of([0xff, 0x00, 0x01]), Some((&[0xff, 0x00][..], &1)));
+    }
+
+    #[test]
+    fn path_keys() {
+        let mut map = PathMap::new();
+        map.put("/usr/lib", 1);
+        map.put(PathBuf::from("/usr"), 2);
+        map.put(Path::new("/etc"), 3);
+        assert_eq!(map.binary_search("/usr"), Some(&2));
+        assert_eq!(map.key(0), Some(Path::new("/etc")));
+        let found = map.longest_prefix_of("/usr/lib/libc.so");
+        assert_eq!(found, Some((Path::new("/usr/lib"), &1)));
+        let mut os = OsStrMap::new();
+        os.put("key", ());
+        assert!(os.contains_key(OsStr::new("key")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = OsStr::from_bytes(b"/tmp/\xff");
+        let map: PathMap<u32> = vec![(Path::new(name), 1)].into();
+        assert_eq!(map.get(0), Some((Path::new(name), &1)));
+    }
+}
@@ -0,0 +1,335 @@
+//! Binary layout of byte-keyed maps which can be used in place
+//!
+//! ```text
+//! magic       4 bytes  b"SMAP"
+//! version     u8       1
+//! byte order  u8       0 little endian, 1 big endian, applies to values only
+//! value size  u16 LE   size of V in bytes
+//! len         u64 LE   number of records
+//! keys len    u64 LE   length of the key buffer
+//! offsets     (len + 1) x u64 LE, start of every key and the end of the last one
+//! values      len x value size, in the byte order of the writer
+//! keys        keys len bytes
+//! ```
+//!
+//! Opening a blob only checks the header and section sizes, so it takes constant time.
+//! Every access is bounds checked, a corrupted blob gives wrong answers but is never UB
+
+use super::{KeyedMap, SliceMap};
+use core::cmp::Ordering;
+use core::convert::TryInto;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::slice;
+use core::str;
+
+const MAGIC: &[u8; 4] = b"SMAP";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 24;
+
+#[cfg(target_endian = "little")]
+const BYTE_ORDER: u8 = 0;
+#[cfg(target_endian = "big")]
+const BYTE_ORDER: u8 = 1;
+
+/// Plain old data which is stored as its in-memory bytes
+///
+/// # Safety
+///
+/// The type must have no padding bytes and every bit pattern must be a valid value
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => { $(unsafe impl Pod for $t {})* };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The blob does not start with the magic bytes
+    Magic,
+    Version(u8),
+    /// The values were written on a machine with another byte order
+    ByteOrder,
+    ValueSize { expected: usize, found: usize },
+    /// The blob is shorter than its header says
+    Truncated,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Magic => f.write_str("not a slice map blob"),
+            LayoutError::Version(v) => write!(f, "unsupported slice map version {}", v),
+            LayoutError::ByteOrder => f.write_str("slice map values have another byte order"),
+            LayoutError::ValueSize { expected, found } => {
+                write!(f, "slice map value size is {}, expected {}", found, expected)
+            }
+            LayoutError::Truncated => f.write_str("slice map blob is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl<V: Pod> SliceMap<u8, V> {
+    /// Writes the map in the versioned binary layout read by [SliceMapRef]
+    ///
+    /// # Panics
+    ///
+    /// If the values are larger than 65535 bytes, the size limit of the header
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = mem::size_of::<V>();
+        assert!(size <= u16::MAX as usize, "slice map values of {} bytes are too large", size);
+        let len = self.map.len();
+        let mut out = Vec::with_capacity(HEADER_LEN + (len + 1) * 8 + len * size + self.buf.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(BYTE_ORDER);
+        out.extend_from_slice(&(size as u16).to_le_bytes());
+        out.extend_from_slice(&(len as u64).to_le_bytes());
+        out.extend_from_slice(&(self.buf.len() as u64).to_le_bytes());
+        let ends = core::iter::once(self.buf.len());
+        for offset in self.map.iter().map(|r| r.0).chain(ends) {
+            out.extend_from_slice(&(offset as u64).to_le_bytes());
+        }
+        for (_, v) in &self.map {
+            // Pod values have no padding, so all their bytes are initialized
+            let bytes = unsafe { slice::from_raw_parts(v as *const V as *const u8, size) };
+            out.extend_from_slice(bytes);
+        }
+        out.extend_from_slice(&self.buf);
+        out
+    }
+}
+
+impl<Q: ?Sized, V: Pod> KeyedMap<Q, V> {
+    /// Writes the map in the versioned binary layout, see [SliceMap::to_bytes]
+    ///
+    /// The layout does not record the key type: read maps with `str` keys
+    /// with [StringMapRef] and all other maps with [SliceMapRef]
+    #[inline]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+
+/// A read-only byte-keyed map borrowed from a blob written by [SliceMap::to_bytes]
+#[derive(Clone, Copy, Debug)]
+pub struct SliceMapRef<'a, V> {
+    offsets: &'a [u8],
+    values: &'a [u8],
+    keys: &'a [u8],
+    len: usize,
+    value: PhantomData<V>,
+}
+
+impl<'a, V: Pod> SliceMapRef<'a, V> {
+    /// Opens the blob checking only its header and section sizes
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, LayoutError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(LayoutError::Magic);
+        }
+        if bytes[4] != VERSION {
+            return Err(LayoutError::Version(bytes[4]));
+        }
+        let size = mem::size_of::<V>();
+        let found = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        if found != size {
+            return Err(LayoutError::ValueSize { expected: size, found });
+        }
+        if bytes[5] != BYTE_ORDER && size > 1 {
+            return Err(LayoutError::ByteOrder);
+        }
+        let len = read_u64(&bytes[8..16]).ok_or(LayoutError::Truncated)?;
+        let keys_len = read_u64(&bytes[16..24]).ok_or(LayoutError::Truncated)?;
+        let offsets_len = len.checked_add(1).and_then(|n| n.checked_mul(8));
+        let values_len = len.checked_mul(size);
+        let (offsets, rest) = split(&bytes[HEADER_LEN..], offsets_len)?;
+        let (values, rest) = split(rest, values_len)?;
+        let (keys, _) = split(rest, Some(keys_len))?;
+        Ok(Self { offsets, values, keys, len, value: PhantomData })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&'a [u8], V)> {
+        Some((self.key(index)?, self.value(index)?))
+    }
+
+    pub fn key(&self, index: usize) -> Option<&'a [u8]> {
+        let start = self.offset(index)?;
+        let end = self.offset(index + 1)?;
+        self.keys.get(start..end)
+    }
+
+    pub fn value(&self, index: usize) -> Option<V> {
+        let size = mem::size_of::<V>();
+        let bytes = self.values.get(index.checked_mul(size)?..)?.get(..size)?;
+        // any bytes are a valid Pod value, and the read does not need alignment
+        Some(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const V) })
+    }
+
+    /// Binary search of the key, returns the index of the record or
+    /// the index where a record with the key could be inserted
+    pub fn search(&self, k: &[u8]) -> Result<usize, usize> {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.key(mid).unwrap_or_default().cmp(k) {
+                Ordering::Equal => return Ok(mid),
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+            }
+        }
+        Err(lo)
+    }
+
+    #[inline]
+    pub fn get_by_key(&self, k: &[u8]) -> Option<V> {
+        self.search(k).ok().and_then(|i| self.value(i))
+    }
+
+    #[inline]
+    fn offset(&self, index: usize) -> Option<usize> {
+        let bytes = self.offsets.get(index.checked_mul(8)?..)?;
+        read_u64(bytes.get(..8)?)
+    }
+}
+
+/// A read-only [StringMap](super::StringMap) borrowed from a blob written by [KeyedMap::to_bytes]
+///
+/// Keys are checked to be UTF-8 when they are read, invalid keys are treated as missing
+#[derive(Clone, Copy, Debug)]
+pub struct StringMapRef<'a, V>(SliceMapRef<'a, V>);
+
+impl<'a, V: Pod> StringMapRef<'a, V> {
+    /// Opens the blob checking only its header and section sizes
+    #[inline]
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, LayoutError> {
+        SliceMapRef::from_bytes(bytes).map(Self)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&'a str, V)> {
+        Some((self.key(index)?, self.value(index)?))
+    }
+
+    pub fn key(&self, index: usize) -> Option<&'a str> {
+        str::from_utf8(self.0.key(index)?).ok()
+    }
+
+    #[inline]
+    pub fn value(&self, index: usize) -> Option<V> {
+        self.0.value(index)
+    }
+
+    #[inline]
+    pub fn binary_search<S: AsRef<str>>(&self, s: S) -> Option<V> {
+        self.0.get_by_key(s.as_ref().as_bytes())
+    }
+}
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> Option<usize> {
+    u64::from_le_bytes(bytes.try_into().ok()?).try_into().ok()
+}
+
+#[inline]
+fn split(bytes: &[u8], len: Option<usize>) -> Result<(&[u8], &[u8]), LayoutError> {
+    match len {
+        Some(len) if len <= bytes.len() => Ok(bytes.split_at(len)),
+        _ => Err(LayoutError::Truncated),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::slicemap::{BytesMap, StringMap};
+
+    #[test]
+    fn string_map_round_trip() {
+        let mut map = StringMap::new();
+        for (i, k) in ["delta", "alpha", "charlie", "bravo", ""].iter().enumerate() {
+            map.put(k, [i as u32, 100 + i as u32]);
+        }
+        let bytes = map.to_bytes();
+        let view = StringMapRef::<[u32; 2]>::from_bytes(&bytes).unwrap();
+        assert_eq!(view.len(), 5);
+        for i in 0..map.len() {
+            let (k, v) = map.get(i).unwrap();
+            assert_eq!(view.get(i), Some((k, *v)));
+            assert_eq!(view.binary_search(k), Some(*v));
+        }
+        assert_eq!(view.key(0), Some(""));
+        assert_eq!(view.binary_search("echo"), None);
+        assert_eq!(view.get(5), None);
+        // values are read unaligned, so the blob may start anywhere
+        let shifted = [&[0][..], &bytes].concat();
+        let view = StringMapRef::<[u32; 2]>::from_bytes(&shifted[1..]).unwrap();
+        assert_eq!(view.binary_search("charlie"), Some([2, 102]));
+    }
+
+    #[test]
+    fn bytes_map_round_trip() {
+        let map: BytesMap<u16> = vec![(&b"\xff"[..], 1), (b"\0", 2)].into();
+        let bytes = map.to_bytes();
+        let view = SliceMapRef::<u16>::from_bytes(&bytes).unwrap();
+        assert_eq!(view.get(0), Some((&b"\0"[..], 2)));
+        assert_eq!(view.get_by_key(b"\xff"), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "too large")]
+    fn rejects_oversized_values() {
+        let mut map = SliceMap::new();
+        map.put(b"key".iter().copied(), [0u8; 1 << 16]);
+        map.to_bytes();
+    }
+
+    #[test]
+    fn rejects_invalid_blobs() {
+        let mut map = StringMap::new();
+        map.put("key", 1u32);
+        let bytes = map.to_bytes();
+        assert_eq!(StringMapRef::<u32>::from_bytes(b"SMAP").err(), Some(LayoutError::Magic));
+        let err = StringMapRef::<u64>::from_bytes(&bytes).err();
+        assert_eq!(err, Some(LayoutError::ValueSize { expected: 8, found: 4 }));
+        let truncated = &bytes[..bytes.len() - 1];
+        let err = StringMapRef::<u32>::from_bytes(truncated).err();
+        assert_eq!(err, Some(LayoutError::Truncated));
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        let err = StringMapRef::<u32>::from_bytes(&newer).err();
+        assert_eq!(err, Some(LayoutError::Version(2)));
+        let mut corrupted = bytes;
+        corrupted[HEADER_LEN] = 0xff;
+        let view = StringMapRef::<u32>::from_bytes(&corrupted).unwrap();
+        assert_eq!(view.get(0), None);
+        assert_eq!(view.binary_search("key"), None);
+    }
+}
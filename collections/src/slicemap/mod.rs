@@ -0,0 +1,449 @@
+use core::cmp::Ordering;
+use core::mem;
+use core::iter::FusedIterator;
+use core::ops::{Bound, Range, RangeBounds};
+#[cfg(feature = "serde_derive")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+mod intern;
+mod keyed;
+mod layout;
+pub use intern::{FrozenInterner, Interner};
+pub use keyed::{
+    ByteKey, BytesMap, DuplicateKeyError, KeyedIter, KeyedMap, OsStrMap, PathMap, StrIter,
+    StringMap,
+};
+pub use layout::{LayoutError, Pod, SliceMapRef, StringMapRef};
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+pub struct SliceMap<K, V> {
+    buf: Vec<K>,
+    map: Vec<(usize, V)>,
+}
+
+/// The serialized form of [SliceMap]
+#[cfg(feature = "serde_derive")]
+#[derive(Deserialize)]
+#[serde(rename = "SliceMap")]
+struct SliceRecords<K, V> {
+    buf: Vec<K>,
+    map: Vec<(usize, V)>,
+}
+
+/// Rejects maps with key offsets which are out of order or out of the buffer,
+/// unsorted keys are accepted and can be sorted with [SliceMap::sort]
+#[cfg(feature = "serde_derive")]
+impl<'de, K, V> Deserialize<'de> for SliceMap<K, V>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let SliceRecords { buf, map } = SliceRecords::<K, V>::deserialize(d)?;
+        let map = Self { buf, map };
+        match map.has_valid_offsets() {
+            true => Ok(map),
+            false => Err(D::Error::custom("slice map key offsets are out of order")),
+        }
+    }
+}
+
+impl<K, V> SliceMap<K, V> {
+    pub fn new() -> Self {
+        let buf = Vec::new();
+        let map = Vec::new();
+        Self { buf, map }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        let buf = Vec::with_capacity(cap);
+        let map = Vec::new();
+        Self { buf, map }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&[K], &V)> {
+        let value = self.value(index)?;
+        let key = self.key(index)?;
+        Some((key, value))
+    }
+
+    pub fn key(&self, index: usize) -> Option<&[K]> {
+        let start = self.key_offset(index)?;
+        let end = self.key_offset(index + 1).unwrap_or_else(|| self.buf.len());
+        Some(&self.buf[start..end])
+    }
+
+    pub fn value(&self, index: usize) -> Option<&V> {
+        self.map.get(index).map(|i| &i.1)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { map: self, range: 0..self.len() }
+    }
+
+    /// Appends the record without keeping keys sorted
+    ///
+    /// Keys put out of order must be sorted with [SliceMap::sort] before key lookups,
+    /// see [SliceMap::insert] to keep them sorted on every insertion
+    pub fn put<I: Iterator<Item = K>>(&mut self, k: I, v: V) {
+        self.map.push((self.buf.len(), v));
+        self.buf.extend(k);
+    }
+
+    /// Checks that key offsets are ascending and within the buffer
+    fn has_valid_offsets(&self) -> bool {
+        let ends = self.map.iter().skip(1).map(|r| r.0).chain(Some(self.buf.len()));
+        self.map.iter().zip(ends).all(|(r, end)| r.0 <= end)
+    }
+
+    fn key_offset(&self, index: usize) -> Option<usize> {
+        self.map.get(index).map(|i| i.0)
+    }
+
+    /// Returns the buffer range of the key, panics if the index is out of bounds
+    #[inline]
+    fn key_range(&self, index: usize) -> Range<usize> {
+        let end = self.key_offset(index + 1).unwrap_or(self.buf.len());
+        self.map[index].0..end
+    }
+}
+
+impl<K: Ord, V> SliceMap<K, V> {
+    /// Inserts the record keeping keys sorted, replaces and returns
+    /// the old value if the key is already present
+    ///
+    /// Keys inserted in ascending order are appended without moving the buffer
+    pub fn insert(&mut self, k: &[K], v: V) -> Option<V>
+    where K: Clone
+    {
+        let start = self.buf.len();
+        self.put(k.iter().cloned(), v);
+        let last = self.map.len() - 1;
+        let key = self.key_range(last);
+        let prev = last.checked_sub(1).map(|i| self.key_range(i));
+        let found = match prev.map(|p| self.buf[p].cmp(&self.buf[key.clone()])) {
+            None | Some(Ordering::Less) => return None,
+            Some(_) => self.search_in(&self.buf[key], last),
+        };
+        let (_, v) = self.map.pop().unwrap();
+        match found {
+            Ok(i) => {
+                self.buf.truncate(start);
+                Some(mem::replace(&mut self.map[i].1, v))
+            }
+            Err(i) => {
+                let len = self.buf.len() - start;
+                let offset = self.map[i].0;
+                self.buf[offset..].rotate_right(len);
+                self.map[i..].iter_mut().for_each(|r| r.0 += len);
+                self.map.insert(i, (offset, v));
+                None
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get_by_key(&self, k: &[K]) -> Option<&V> {
+        self.search(k).ok().and_then(|i| self.value(i))
+    }
+
+    #[inline]
+    pub fn contains_key(&self, k: &[K]) -> bool {
+        self.search(k).is_ok()
+    }
+
+    /// Removes the record of the key and returns its value
+    pub fn remove(&mut self, k: &[K]) -> Option<V> {
+        let index = self.search(k).ok()?;
+        let key = self.key_range(index);
+        let len = key.len();
+        self.buf.drain(key);
+        self.map[index + 1..].iter_mut().for_each(|r| r.0 -= len);
+        Some(self.map.remove(index).1)
+    }
+
+    /// Binary search of the key, returns the index of the record or
+    /// the index where a record with the key could be inserted
+    #[inline]
+    pub fn search(&self, k: &[K]) -> Result<usize, usize> {
+        self.search_in(k, self.map.len())
+    }
+
+    /// Checks that keys are strictly ascending, which lookups rely on
+    ///
+    /// It fails for maps with keys put out of order, see [SliceMap::sort]
+    pub fn is_sorted(&self) -> bool {
+        (1..self.map.len()).all(|i| self.buf[self.key_range(i - 1)] < self.buf[self.key_range(i)])
+    }
+
+    /// Sorts the records by key and rebuilds the buffer,
+    /// only the last value of duplicate keys is kept
+    pub fn sort(&mut self)
+    where K: Clone
+    {
+        if self.is_sorted() {
+            return;
+        }
+        let keys: Vec<_> = (0..self.map.len()).map(|i| self.key_range(i)).collect();
+        let mut records: Vec<_> = keys.into_iter().zip(self.map.drain(..).map(|r| r.1)).collect();
+        let buf = mem::take(&mut self.buf);
+        // the stable sort keeps duplicates in insertion order, reversed they keep the last one
+        records.sort_by(|a, b| buf[a.0.clone()].cmp(&buf[b.0.clone()]));
+        records.reverse();
+        records.dedup_by(|a, b| buf[a.0.clone()] == buf[b.0.clone()]);
+        self.buf.reserve(buf.len());
+        self.map.reserve(records.len());
+        for (key, v) in records.into_iter().rev() {
+            self.map.push((self.buf.len(), v));
+            self.buf.extend_from_slice(&buf[key]);
+        }
+    }
+
+    /// Iterates over the records with keys within the range in ascending order
+    pub fn range<R: RangeBounds<[K]>>(&self, range: R) -> Iter<'_, K, V> {
+        let start = match range.start_bound() {
+            Bound::Included(k) => self.lower_bound(k),
+            Bound::Excluded(k) => self.upper_bound(k),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => self.upper_bound(k),
+            Bound::Excluded(k) => self.lower_bound(k),
+            Bound::Unbounded => self.len(),
+        };
+        Iter { map: self, range: start..end.max(start) }
+    }
+
+    /// Iterates over the records with keys starting with the prefix in ascending order
+    pub fn prefix(&self, p: &[K]) -> Iter<'_, K, V> {
+        let start = self.lower_bound(p);
+        let end = self.partition_point(start..self.len(), |k| k.starts_with(p));
+        Iter { map: self, range: start..end }
+    }
+
+    /// Finds the record with the longest key which is a prefix of `k`
+    pub fn longest_prefix_of(&self, k: &[K]) -> Option<(&[K], &V)> {
+        let mut len = k.len();
+        loop {
+            // the longest prefix is between the greatest key not above `k` and `k` itself,
+            // so it is also a prefix of their common part
+            let i = self.upper_bound(&k[..len]).checked_sub(1)?;
+            let key = &self.buf[self.key_range(i)];
+            if k.starts_with(key) {
+                return self.get(i);
+            }
+            len = key.iter().zip(k).take_while(|(a, b)| a == b).count();
+        }
+    }
+
+    /// Returns the index of the first key not less than `k`
+    #[inline]
+    pub fn lower_bound(&self, k: &[K]) -> usize {
+        self.partition_point(0..self.len(), |key| key < k)
+    }
+
+    /// Returns the index of the first key greater than `k`
+    #[inline]
+    pub fn upper_bound(&self, k: &[K]) -> usize {
+        self.partition_point(0..self.len(), |key| key <= k)
+    }
+
+    fn partition_point<P>(&self, range: Range<usize>, mut pred: P) -> usize
+    where P: FnMut(&[K]) -> bool
+    {
+        let (mut lo, mut hi) = (range.start, range.end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if pred(&self.buf[self.key_range(mid)]) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Builds the map from records with unique keys, fails on the first duplicate key
+    pub fn try_from_iter<Q, I>(iter: I) -> Result<Self, DuplicateKeyError<Q>>
+    where
+        Q: AsRef<[K]>,
+        I: IntoIterator<Item = (Q, V)>,
+        K: Clone,
+    {
+        let mut src: Vec<_> = iter.into_iter().enumerate().collect();
+        // the stable sort keeps duplicates in the input order
+        src.sort_by(|(_, a), (_, b)| a.0.as_ref().cmp(b.0.as_ref()));
+        let key = |i: usize| src[i].1 .0.as_ref();
+        if let Some(i) = (1..src.len()).find(|i| key(i - 1) == key(*i)) {
+            let first = src[i - 1].0;
+            let (second, (key, _)) = src.swap_remove(i);
+            return Err(DuplicateKeyError { key, first, second });
+        }
+        let mut map = Self::with_capacity(src.iter().map(|(_, r)| r.0.as_ref().len()).sum());
+        for (_, (k, v)) in src {
+            map.put(k.as_ref().iter().cloned(), v);
+        }
+        Ok(map)
+    }
+
+    fn search_in(&self, k: &[K], len: usize) -> Result<usize, usize> {
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.buf[self.key_range(mid)].cmp(k) {
+                Ordering::Equal => return Ok(mid),
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+            }
+        }
+        Err(lo)
+    }
+}
+
+/// An iterator over a range of records of a [SliceMap] in ascending key order
+pub struct Iter<'m, K, V> {
+    map: &'m SliceMap<K, V>,
+    range: Range<usize>,
+}
+
+impl<'m, K, V> Iterator for Iter<'m, K, V> {
+    type Item = (&'m [K], &'m V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().and_then(|i| self.map.get(i))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back().and_then(|i| self.map.get(i))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use random::Source;
+
+    fn smap() -> StringMap<u32> {
+        StringMap::new()
+    }
+
+    #[test]
+    fn map_put_and_find() {
+        let mut map = smap();
+        map.put("word3", 3);
+        map.put("word2", 2);
+        map.put("word5", 5);
+        map.put("word4", 4);
+        map.put("word1", 1);
+        println!("{:?}", map);
+        for i in 1..6 {
+            let key = &format!("word{}", i);
+            assert_eq!(map.binary_search(key), Some(&i));
+        }
+    }
+
+    #[test]
+    fn map_put_replace_and_remove() {
+        let mut map = smap();
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("abc", 1), None);
+        assert_eq!(map.insert("ab", 0), None);
+        assert_eq!(map.insert("b", 3), Some(2));
+        assert!(map.is_sorted());
+        let keys: Vec<_> = (0..map.len()).filter_map(|i| map.key(i)).collect();
+        assert_eq!(keys, vec!["ab", "abc", "b"]);
+        assert_eq!(map.remove("abc"), Some(1));
+        assert_eq!(map.remove("abc"), None);
+        assert!(!map.contains_key("abc"));
+        assert_eq!(map.get(1), Some(("b", &3)));
+        assert_eq!(map.binary_search("ab"), Some(&0));
+    }
+
+    #[test]
+    fn slice_map_against_model() {
+        let mut map = SliceMap::new();
+        let mut model = std::collections::BTreeMap::new();
+        let mut source = random::default(42);
+        for i in 0..2000 {
+            let x = source.read_u64();
+            let key: Vec<u8> = (0..x % 4).map(|j| (x >> (8 + j * 2)) as u8 % 4).collect();
+            if x % 5 < 2 {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(&key, i), model.insert(key, i));
+            }
+        }
+        assert!(map.is_sorted());
+        let pairs: Vec<_> = (0..map.len()).filter_map(|i| map.get(i)).collect();
+        let expected: Vec<_> = model.iter().map(|(k, v)| (&k[..], v)).collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn sort_rebuilds_unsorted_buffer() {
+        let mut map = SliceMap::new();
+        for (k, v) in [&b"c"[..], b"ab", b"c", b"ab"].iter().zip(1..) {
+            map.put(k.iter().copied(), v);
+        }
+        assert!(!map.is_sorted());
+        map.sort();
+        assert!(map.is_sorted());
+        assert_eq!(map.get_by_key(b"ab"), Some(&4));
+        assert_eq!(map.get_by_key(b"c"), Some(&3));
+        assert_eq!(map.get_by_key(b"bc"), None);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn prefix_and_range() {
+        let mut map = smap();
+        for (i, k) in ["app", "apple", "apply", "b", "ap", "banana"].iter().enumerate() {
+            map.put(k, i as u32);
+        }
+        fn keys<'m>(it: StrIter<'m, u32>) -> Vec<&'m str> {
+            it.map(|(k, _)| k).collect()
+        }
+        assert_eq!(keys(map.prefix("app")), vec!["app", "apple", "apply"]);
+        assert_eq!(keys(map.prefix("appl")), vec!["apple", "apply"]);
+        assert_eq!(keys(map.prefix("c")), Vec::<&str>::new());
+        assert_eq!(map.prefix("").len(), 6);
+        assert_eq!(keys(map.range("apple".."b")), vec!["apple", "apply"]);
+        assert_eq!(keys(map.range("apq"..)), vec!["b", "banana"]);
+        assert_eq!(keys(map.range(..="ap")), vec!["ap"]);
+        assert_eq!(map.iter().next_back(), Some(("banana", &5)));
+    }
+
+    #[test]
+    fn longest_prefix() {
+        let mut routes = smap();
+        for (i, r) in ["/", "/api/", "/api/v1/", "/apiv2", "/static/"].iter().enumerate() {
+            routes.put(r, i as u32);
+        }
+        assert_eq!(routes.longest_prefix_of("/api/v1/users"), Some(("/api/v1/", &2)));
+        assert_eq!(routes.longest_prefix_of("/api/v2/users"), Some(("/api/", &1)));
+        assert_eq!(routes.longest_prefix_of("/apiv"), Some(("/", &0)));
+        assert_eq!(routes.longest_prefix_of("/static/"), Some(("/static/", &4)));
+        assert_eq!(routes.longest_prefix_of("static"), None);
+        routes.put("", 5);
+        assert_eq!(routes.longest_prefix_of("static"), Some(("", &5)));
+    }
+}
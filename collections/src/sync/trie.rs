@@ -1,79 +1,770 @@
-use shared::{RcCell, Ptr};
-use crate::btree::Map;
+//! A persistent trie and its concurrent wrapper
+//!
+//! Nodes are reference counted, so cloning a [Trie] is O(1) and the clones share all
+//! nodes. A write copies the shared nodes on the path from the root to the changed node
+//! and leaves every other node shared, so a clone works as an immutable snapshot.
+//!
+//! [SyncTrie] keeps the current trie in a [RcCell]. Readers take a snapshot of the root
+//! without blocking and traverse it while writers go on. Writers are serialized, copy the
+//! path of the change and publish the new root with an atomic swap. Every operation takes
+//! effect at the swap or snapshot of the root, so operations are linearizable, and a
+//! snapshot sees either all or nothing of every write.
+
+use crate::btree::{Compare, Map, MapIter, Natural};
+use crate::edit::EditRows;
+use core::borrow::Borrow;
+use core::cmp;
+use core::iter::FusedIterator;
+pub use crate::edit::Metric;
+use shared::{Rc, RcCell};
+use std::collections::BinaryHeap;
 
 pub trait Key: Ord { }
 
 impl<T: Ord> Key for T { }
 
-pub struct Trie<K, V> {
-    root: NodeRef<K, V>,
-    len: usize
+/// A trie with children of every node ordered by the comparator `C`
+///
+/// Keys are sequences of `K`, a lookup accepts any iterator over
+/// `K` or `&K`, e.g. a slice or `str::chars`
+pub struct Trie<K, V, C = Natural> {
+    root: Rc<Node<K, V, C>>,
+}
+
+impl<K, V, C> Clone for Trie<K, V, C> {
+    /// Shares all nodes with the clone, the next writes copy the paths they change
+    fn clone(&self) -> Self {
+        Self { root: self.root.clone() }
+    }
 }
 
 impl<K: Key, V> Trie<K, V> {
     pub fn new() -> Self {
-        let root = NodeRef::new(Node::new());
-        let len = 0;
-        Trie { root, len }
+        Self::with_comparator()
     }
 }
 
-struct NodeRef<K, V>(RcCell<Node<K, V>>);
+impl<K: Key, V> Default for Trie<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-impl<K, V> Clone for NodeRef<K, V> {
-    fn clone(&self) -> Self {
-        Self(self.0.clone())
+impl<K, V, C: Compare<K>> Trie<K, V, C> {
+    pub fn with_comparator() -> Self {
+        let root = Rc::new(Node::new());
+        Trie { root }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.root.count
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.root.count == 0
+    }
+
+    pub fn get<I>(&self, keys: I) -> Option<&V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        self.node(keys)?.value.as_ref()
+    }
+
+    #[inline]
+    pub fn contains_key<I>(&self, keys: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        self.get(keys).is_some()
+    }
+
+    /// Checks if any key starts with the prefix
+    #[inline]
+    pub fn contains_prefix<I>(&self, prefix: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        self.count_prefix(prefix) > 0
+    }
+
+    /// Counts the keys starting with the prefix in O(prefix length)
+    pub fn count_prefix<I>(&self, prefix: I) -> usize
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        self.node(prefix).map_or(0, |node| node.count)
+    }
+
+    /// Finds the value of the longest key which is a prefix of `keys`
+    /// and returns it with the length of that key
+    pub fn longest_common_prefix<I>(&self, keys: I) -> Option<(usize, &V)>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        let mut node = &*self.root;
+        let mut found = node.value.as_ref().map(|v| (0, v));
+        for (i, k) in keys.into_iter().enumerate() {
+            node = match node.children.get(k.borrow()) {
+                Some(child) => child,
+                None => break,
+            };
+            if let Some(v) = &node.value {
+                found = Some((i + 1, v));
+            }
+        }
+        found
+    }
+
+    fn node<I>(&self, keys: I) -> Option<&Node<K, V, C>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        let mut node = &*self.root;
+        for k in keys {
+            node = node.children.get(k.borrow())?;
+        }
+        Some(node)
     }
 }
 
-impl<K: Key, V> NodeRef<K, V> {
-    pub fn new(node: Node<K, V>) -> Self {
-        Self(RcCell::new(node))
+/// Writes copy the nodes shared with clones of the trie, so they need `K` and `V` to be [Clone]
+impl<K: Clone, V: Clone, C: Compare<K>> Trie<K, V, C> {
+    pub fn clear(&mut self) {
+        self.root = Rc::new(Node::new());
+    }
+
+    /// Inserts the value and returns the previous value of the key
+    pub fn insert<I>(&mut self, keys: I, v: V) -> Option<V>
+    where I: IntoIterator<Item = K>
+    {
+        let keys: Vec<K> = keys.into_iter().collect();
+        // the counts on the path are updated on the way down
+        let added = !self.contains_key(&keys) as usize;
+        let mut node = Rc::make_mut(&mut self.root);
+        node.count += added;
+        for k in keys {
+            node = node.get_or_add_child(k);
+            node.count += added;
+        }
+        node.value.replace(v)
     }
 
-    pub fn insert<I>(&mut self, mut keys: I, v: V) -> Option<V>
-    where I: Iterator<Item = K>
+    pub fn get_mut<I>(&mut self, keys: I) -> Option<&mut V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
     {
-        if let Some(k) = keys.next() {
-            self.0.get()
-                .get_or_add_child(k)
-                .insert(keys, v)
-        } else {
-            self.0.get()
-                .set_value(v)
+        let keys: Vec<_> = keys.into_iter().collect();
+        self.get(keys.iter().map(Borrow::borrow))?;
+        let mut node = Rc::make_mut(&mut self.root);
+        for k in keys {
+            node = Rc::make_mut(node.children.get_mut(k.borrow())?);
         }
+        node.value.as_mut()
+    }
+
+    /// Removes the key and prunes the branches left without values
+    pub fn remove<I>(&mut self, keys: I) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        let keys: Vec<_> = keys.into_iter().collect();
+        self.get(keys.iter().map(Borrow::borrow))?;
+        Rc::make_mut(&mut self.root).remove(&keys)
     }
 }
 
-struct Node<K, V> {
-    value: Ptr<V>,
-    children: Map<K, NodeRef<K, V>>
+impl<K: Clone, V, C> Trie<K, V, C> {
+    /// Iterates over the entries in key order, a key comes before the keys it prefixes
+    pub fn iter(&self) -> Iter<'_, K, V, C> {
+        Iter::new(&self.root, Vec::new())
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>> Trie<K, V, C> {
+    /// Iterates over the entries with keys starting with the prefix in key order
+    pub fn iter_prefix<I>(&self, prefix: I) -> Iter<'_, K, V, C>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        let mut node = &*self.root;
+        let mut key = Vec::new();
+        for k in prefix {
+            match node.children.get(k.borrow()) {
+                Some(child) => node = child,
+                None => return Iter::empty(),
+            }
+            key.push(k.borrow().clone());
+        }
+        Iter::new(node, key)
+    }
+
+    /// Returns the `k` entries under the prefix with the highest scores, best first
+    ///
+    /// Entries with equal scores come in key order. Every entry under the prefix is scored
+    pub fn complete<I, S, F>(&self, prefix: I, k: usize, mut score: F) -> Vec<(Vec<K>, &V)>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+        S: Ord,
+        F: FnMut(&[K], &V) -> S,
+    {
+        let entries = self.iter_prefix(prefix);
+        if k == 0 {
+            return Vec::new();
+        }
+        // a min-heap of the best entries so far, ranked by score and then by key order
+        let mut best = BinaryHeap::with_capacity(k.min(entries.len()) + 1);
+        for (i, (key, v)) in entries.enumerate() {
+            let rank = (score(&key, v), cmp::Reverse(i));
+            if best.len() == k {
+                if matches!(best.peek(), Some(Ranked(cmp::Reverse(worst), _)) if *worst >= rank) {
+                    continue;
+                }
+                best.pop();
+            }
+            best.push(Ranked(cmp::Reverse(rank), (key, v)));
+        }
+        let mut best = best.into_vec();
+        best.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        best.into_iter().map(|Ranked(_, entry)| entry).collect()
+    }
+
+    /// Finds the keys within `max` edits of the query, in key order with their distances
+    ///
+    /// Walks the trie with a row of the edit distance table per node and skips the
+    /// subtrees where every entry of the row exceeds `max`
+    pub fn fuzzy<Q>(&self, query: &[Q], max: usize, metric: Metric) -> Vec<(Vec<K>, usize, &V)>
+    where Q: Borrow<K>
+    {
+        let rows = EditRows::new(query, max, metric);
+        let row = rows.first();
+        let mut search = Fuzzy { rows, key: Vec::new(), found: Vec::new() };
+        if let (Some(v), Some(d)) = (&self.root.value, search.rows.distance(&row)) {
+            search.found.push((Vec::new(), d, v));
+        }
+        search.visit(&self.root, &[], &row);
+        search.found
+    }
+}
+
+/// The state of a fuzzy search below the root
+struct Fuzzy<'q, 't, Q, K, V> {
+    rows: EditRows<'q, Q>,
+    /// The key of the visited node
+    key: Vec<K>,
+    found: Vec<(Vec<K>, usize, &'t V)>,
+}
+
+impl<'t, Q: Borrow<K>, K: Clone, V> Fuzzy<'_, 't, Q, K, V> {
+    /// Visits the children of the node, `row` holds the distances of its key to the prefixes
+    /// of the query and `prev` those of its parent
+    fn visit<C: Compare<K>>(&mut self, node: &'t Node<K, V, C>, prev: &[usize], row: &[usize]) {
+        let eq = |a: &K, b: &Q| C::compare(a, b.borrow()) == cmp::Ordering::Equal;
+        for (k, child) in node.children.iter() {
+            let next = match self.rows.next(prev, row, self.key.last(), k, eq) {
+                Some(next) => next,
+                None => continue,
+            };
+            self.key.push(k.clone());
+            if let (Some(v), Some(d)) = (&child.value, self.rows.distance(&next)) {
+                self.found.push((self.key.clone(), d, v));
+            }
+            self.visit(child, row, &next);
+            self.key.pop();
+        }
+    }
+}
+
+/// Orders `T` by the rank `R` only
+struct Ranked<R, T>(R, T);
+
+impl<R: PartialEq, T> PartialEq for Ranked<R, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<R: Eq, T> Eq for Ranked<R, T> {}
+
+impl<R: Ord, T> PartialOrd for Ranked<R, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<R: Ord, T> Ord for Ranked<R, T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<'t, K: Clone, V, C> IntoIterator for &'t Trie<K, V, C> {
+    type Item = (Vec<K>, &'t V);
+    type IntoIter = Iter<'t, K, V, C>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+struct Node<K, V, C> {
+    value: Option<V>,
+    /// Number of values in the subtree of the node
+    count: usize,
+    children: Children<K, V, C>
+}
+
+/// Children are shared with the clones of the trie until a write copies them
+type Children<K, V, C> = Map<K, Rc<Node<K, V, C>>, C>;
+
+type ChildIter<'t, K, V, C> = MapIter<'t, K, Rc<Node<K, V, C>>>;
+
+impl<K: Clone, V: Clone, C> Clone for Node<K, V, C> {
+    fn clone(&self) -> Self {
+        let (value, count, children) = (self.value.clone(), self.count, self.children.clone());
+        Self { value, count, children }
+    }
 }
 
-impl<K: Key, V> Node<K, V> {
+impl<K, V, C: Compare<K>> Node<K, V, C> {
     fn new() -> Self {
-        let value = Ptr::null();
-        let children = Map::new();
-        Self { value, children }
+        let value = None;
+        let children = Map::with_comparator();
+        Self { value, count: 0, children }
+    }
+}
+
+impl<K: Clone, V: Clone, C: Compare<K>> Node<K, V, C> {
+    fn get_or_add_child(&mut self, k: K) -> &mut Node<K, V, C> {
+        Rc::make_mut(self.children.entry(k).or_insert_with(|| Rc::new(Node::new())))
     }
 
-    fn set_value(&mut self, mut v: V) -> Option<V> {
-        if self.value.replace(&mut v) {
-            Some(v)
-        } else {
-            None
+    /// Removes the value of the key below this node, the recursion is as deep as the key
+    fn remove<Q: Borrow<K>>(&mut self, keys: &[Q]) -> Option<V> {
+        let old = match keys.split_first() {
+            None => self.value.take()?,
+            Some((k, rest)) => {
+                let child = Rc::make_mut(self.children.get_mut(k.borrow())?);
+                let old = child.remove(rest)?;
+                if child.count == 0 {
+                    self.children.remove(k.borrow());
+                }
+                old
+            }
+        };
+        self.count -= 1;
+        Some(old)
+    }
+}
+
+/// An iterator over the entries of a [Trie] in key order
+pub struct Iter<'t, K, V, C> {
+    /// Children left to visit at every level below the start node
+    stack: Vec<ChildIter<'t, K, V, C>>,
+    key: Vec<K>,
+    /// The value of the start node, which comes first
+    first: Option<&'t V>,
+    len: usize,
+}
+
+impl<'t, K, V, C> Iter<'t, K, V, C> {
+    /// Iterates over the subtree of the node with keys starting with `key`
+    fn new(node: &'t Node<K, V, C>, key: Vec<K>) -> Self {
+        let stack = vec![node.children.iter()];
+        let first = node.value.as_ref();
+        Self { stack, key, first, len: node.count }
+    }
+
+    fn empty() -> Self {
+        Self { stack: Vec::new(), key: Vec::new(), first: None, len: 0 }
+    }
+}
+
+impl<'t, K: Clone, V, C> Iterator for Iter<'t, K, V, C> {
+    type Item = (Vec<K>, &'t V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(v) = self.first.take() {
+            self.len -= 1;
+            return Some((self.key.clone(), v));
+        }
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some((k, node)) => {
+                    self.key.push(k.clone());
+                    self.stack.push(node.children.iter());
+                    if let Some(v) = &node.value {
+                        self.len -= 1;
+                        return Some((self.key.clone(), v));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.key.pop();
+                }
+            }
         }
     }
 
-    fn child(&self, k: &K) -> Option<&NodeRef<K, V>> {
-        self.children.get(k)
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
+}
+
+impl<K: Clone, V, C> ExactSizeIterator for Iter<'_, K, V, C> {}
+
+impl<K: Clone, V, C> FusedIterator for Iter<'_, K, V, C> {}
+
+/// A [Trie] shared between threads, readers never block and see linearizable snapshots
+///
+/// Writers take a lock only against each other, readers go on while a write copies its path
+pub struct SyncTrie<K, V, C = Natural>(RcCell<Trie<K, V, C>>);
 
-    fn child_mut(&mut self, k: &K) -> Option<&mut NodeRef<K, V>> {
-        self.children.get_mut(k)
+impl<K: Key, V> SyncTrie<K, V> {
+    pub fn new() -> Self {
+        Self::with_comparator()
+    }
+}
+
+impl<K: Key, V> Default for SyncTrie<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, C: Compare<K>> SyncTrie<K, V, C> {
+    pub fn with_comparator() -> Self {
+        Self(RcCell::new(Trie::with_comparator()))
+    }
+
+    /// Returns the current trie, later writes don't change it
+    #[inline]
+    pub fn snapshot(&self) -> Rc<Trie<K, V, C>> {
+        self.0.get()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    pub fn get<I>(&self, keys: I) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+        V: Clone,
+    {
+        self.snapshot().get(keys).cloned()
     }
 
-    fn get_or_add_child(&mut self, k: K) -> &mut NodeRef<K, V> {
-        todo!()
+    #[inline]
+    pub fn contains_key<I>(&self, keys: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        self.snapshot().contains_key(keys)
+    }
+}
+
+impl<K: Clone, V: Clone, C: Compare<K>> SyncTrie<K, V, C> {
+    /// Inserts the value and returns the previous value of the key
+    pub fn insert<I>(&self, keys: I, v: V) -> Option<V>
+    where I: IntoIterator<Item = K>
+    {
+        self.0.update(|trie| Rc::make_mut(trie).insert(keys, v))
+    }
+
+    pub fn remove<I>(&self, keys: I) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        let keys: Vec<_> = keys.into_iter().collect();
+        // a miss leaves the root untouched instead of copying it
+        self.0.update(|trie| match trie.contains_key(keys.iter().map(Borrow::borrow)) {
+            true => Rc::make_mut(trie).remove(keys),
+            false => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use random::Source;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut trie = Trie::new();
+        assert_eq!(trie.insert("tea".chars(), 1), None);
+        assert_eq!(trie.insert("ten".chars(), 2), None);
+        assert_eq!(trie.insert("te".chars(), 3), None);
+        assert_eq!(trie.insert("tea".chars(), 4), Some(1));
+        assert_eq!(trie.len(), 3);
+        assert_eq!(trie.get("tea".chars()), Some(&4));
+        assert_eq!(trie.get("t".chars()), None);
+        assert_eq!(trie.get(&['t', 'e'][..]), Some(&3));
+        *trie.get_mut("ten".chars()).unwrap() += 10;
+        assert!(trie.contains_prefix("t".chars()));
+        assert!(!trie.contains_prefix("to".chars()));
+        assert_eq!(trie.remove("te".chars()), Some(3));
+        assert_eq!(trie.remove("te".chars()), None);
+        assert!(trie.contains_prefix("te".chars()));
+        assert_eq!(trie.remove("tea".chars()), Some(4));
+        assert_eq!(trie.remove("ten".chars()), Some(12));
+        assert!(trie.is_empty());
+        assert!(!trie.contains_prefix("t".chars()));
+        assert!(trie.root.children.is_empty());
+    }
+
+    #[test]
+    fn iterates_in_key_order() {
+        let mut trie = Trie::new();
+        for (i, w) in ["b", "abc", "", "ab", "ba"].iter().enumerate() {
+            trie.insert(w.bytes(), i);
+        }
+        let entries: Vec<_> =
+            trie.iter().map(|(k, v)| (String::from_utf8(k).unwrap(), *v)).collect();
+        let expected = vec![("", 2), ("ab", 3), ("abc", 1), ("b", 0), ("ba", 4)];
+        let expected: Vec<_> = expected.into_iter().map(|(k, v)| (k.to_owned(), v)).collect();
+        assert_eq!(entries, expected);
+        assert_eq!(trie.iter().len(), 5);
+    }
+
+    #[test]
+    fn random_ops_against_model() {
+        let mut trie = Trie::new();
+        let mut model = BTreeMap::new();
+        let mut source = random::default(42);
+        for i in 0..5000 {
+            let x = source.read_u64();
+            let key: Vec<u8> = (0..x % 5).map(|j| (x >> (8 + j * 3)) as u8 % 3).collect();
+            if x % 8 < 2 {
+                assert_eq!(trie.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(trie.insert(key.clone(), i), model.insert(key, i));
+            }
+            assert_eq!(trie.len(), model.len());
+        }
+        for prefix in [&[][..], &[0], &[1, 2], &[2, 2, 0]].iter() {
+            let under = model.iter().filter(|(k, _)| k.starts_with(prefix));
+            let expected: Vec<_> = under.map(|(k, v)| (k.clone(), *v)).collect();
+            let entries: Vec<_> = trie.iter_prefix(*prefix).map(|(k, v)| (k, *v)).collect();
+            assert_eq!(entries, expected);
+            assert_eq!(trie.count_prefix(*prefix), entries.len());
+            assert_eq!(trie.iter_prefix(*prefix).len(), entries.len());
+        }
+        let entries: Vec<_> = trie.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(entries, model.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn prefix_queries() {
+        let mut trie = Trie::new();
+        for (i, w) in ["car", "cart", "carton", "cat", "dog"].iter().enumerate() {
+            trie.insert(w.chars(), i);
+        }
+        let words = |it: Iter<'_, char, usize, Natural>| -> Vec<String> {
+            it.map(|(k, _)| k.into_iter().collect()).collect()
+        };
+        assert_eq!(words(trie.iter_prefix("car".chars())), vec!["car", "cart", "carton"]);
+        assert_eq!(words(trie.iter_prefix("ca".chars())).len(), 4);
+        assert!(words(trie.iter_prefix("cow".chars())).is_empty());
+        assert_eq!(trie.count_prefix("c".chars()), 4);
+        assert_eq!(trie.count_prefix("".chars()), 5);
+        assert_eq!(trie.count_prefix("carts".chars()), 0);
+        assert_eq!(trie.longest_common_prefix("cartography".chars()), Some((4, &1)));
+        assert_eq!(trie.longest_common_prefix("carto".chars()), Some((4, &1)));
+        assert_eq!(trie.longest_common_prefix("ca".chars()), None);
+        trie.insert("".chars(), 5);
+        assert_eq!(trie.longest_common_prefix("ca".chars()), Some((0, &5)));
+        trie.remove("cart".chars());
+        assert_eq!(trie.count_prefix("car".chars()), 2);
+        assert_eq!(trie.longest_common_prefix("cartography".chars()), Some((3, &0)));
+    }
+
+    #[test]
+    fn top_k_completion() {
+        let mut trie = Trie::new();
+        let hits = [("he", 7), ("hello", 3), ("help", 9), ("helm", 3), ("hex", 1), ("io", 20)];
+        for (w, n) in hits.iter() {
+            trie.insert(w.chars(), *n);
+        }
+        let complete = |prefix: &str, k| -> Vec<String> {
+            let best = trie.complete(prefix.chars(), k, |_, n| *n);
+            best.into_iter().map(|(k, _)| k.into_iter().collect()).collect()
+        };
+        assert_eq!(complete("he", 3), vec!["help", "he", "hello"]);
+        assert_eq!(complete("hel", 2), vec!["help", "hello"]);
+        assert_eq!(complete("hel", 10), vec!["help", "hello", "helm"]);
+        assert!(complete("he", 0).is_empty());
+        assert_eq!(complete("he", usize::MAX).len(), 5);
+        assert_eq!(complete("", 1 << 40).len(), 6);
+        assert!(complete("x", 3).is_empty());
+        let shortest = trie.complete("h".chars(), 2, |k, _| cmp::Reverse(k.len()));
+        assert_eq!(shortest.iter().map(|(_, n)| **n).collect::<Vec<_>>(), vec![7, 1]);
+    }
+
+    #[test]
+    fn clones_are_snapshots() {
+        let mut trie = Trie::new();
+        trie.insert("ab".chars(), 1);
+        trie.insert("ac".chars(), 2);
+        let snapshot = trie.clone();
+        trie.insert("ab".chars(), 3);
+        trie.remove("ac".chars());
+        trie.insert("b".chars(), 4);
+        assert_eq!(snapshot.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(trie.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![3, 4]);
+    }
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("{:x}", i.wrapping_mul(0x9e37_79b9)).into_bytes()
+    }
+
+    #[test]
+    fn readers_see_prefixes_of_the_writes() {
+        const N: u32 = 2000;
+        let trie = Arc::new(SyncTrie::new());
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (trie, done) = (trie.clone(), done.clone());
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while !done.load(Ordering::Acquire) {
+                        let snapshot = trie.snapshot();
+                        let len = snapshot.len();
+                        // the writes are ordered, so a snapshot holds exactly the first `len`
+                        assert!(len >= last);
+                        assert_eq!(snapshot.iter().count(), len);
+                        assert!((0..len as u32).all(|i| snapshot.get(key(i)) == Some(&i)));
+                        assert_eq!(snapshot.get(key(len as u32)), None);
+                        // a later read sees at least the writes this snapshot saw
+                        assert!(len == 0 || trie.get(key(len as u32 - 1)).is_some());
+                        last = len;
+                    }
+                })
+            })
+            .collect();
+        for i in 0..N {
+            assert_eq!(trie.insert(key(i), i), None);
+        }
+        done.store(true, Ordering::Release);
+        readers.into_iter().for_each(|r| r.join().unwrap());
+        assert_eq!(trie.len(), N as usize);
+    }
+
+    #[test]
+    fn concurrent_writers_lose_no_updates() {
+        let trie = Arc::new(SyncTrie::new());
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let trie = trie.clone();
+                thread::spawn(move || {
+                    for i in (w..4000).step_by(4) {
+                        assert_eq!(trie.insert(key(i), i), None);
+                        if i % 3 == 0 {
+                            assert_eq!(trie.remove(key(i)), Some(i));
+                        }
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        let snapshot = trie.snapshot();
+        assert_eq!(snapshot.len(), (0..4000).filter(|i| i % 3 != 0).count());
+        assert!(snapshot.iter().all(|(k, v)| k == key(*v) && v % 3 != 0));
+    }
+
+    /// The edit distance table of two strings computed directly
+    fn distance(a: &[u8], b: &[u8], metric: Metric) -> usize {
+        let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..=a.len() {
+            for j in 0..=b.len() {
+                d[i][j] = match (i, j) {
+                    (0, _) | (_, 0) => i + j,
+                    _ => {
+                        let sub = d[i - 1][j - 1] + (a[i - 1] != b[j - 1]) as usize;
+                        sub.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1)
+                    }
+                };
+                let swapped = i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1];
+                if metric == Metric::Damerau && swapped {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+                }
+            }
+        }
+        d[a.len()][b.len()]
+    }
+
+    #[test]
+    fn fuzzy_lookup() {
+        let mut trie = Trie::new();
+        for w in ["form", "from", "fort", "forms", "farm", "foam", "to"].iter() {
+            trie.insert(w.bytes(), *w);
+        }
+        let find = |query: &str, max, metric| -> Vec<(&str, usize)> {
+            let found = trie.fuzzy(query.as_bytes(), max, metric);
+            found.into_iter().map(|(_, d, w)| (*w, d)).collect()
+        };
+        let expected = vec![("farm", 1), ("foam", 1), ("form", 0), ("forms", 1), ("fort", 1)];
+        assert_eq!(find("form", 1, Metric::Levenshtein), expected);
+        assert_eq!(find("from", 0, Metric::Levenshtein), vec![("from", 0)]);
+        assert!(!find("form", 1, Metric::Levenshtein).contains(&("from", 2)));
+        assert!(find("form", 1, Metric::Damerau).contains(&("from", 1)));
+        assert_eq!(find("", 2, Metric::Levenshtein), vec![("to", 2)]);
+        assert!(find("xyz", 1, Metric::Damerau).is_empty());
+    }
+
+    #[test]
+    fn fuzzy_matches_direct_distance() {
+        let mut trie = Trie::new();
+        let mut source = random::default(42);
+        let mut word = || {
+            let x = source.read_u64();
+            (0..x % 7).map(|i| b"abc"[(x >> (8 + i * 2)) as usize % 3]).collect::<Vec<_>>()
+        };
+        for _ in 0..300 {
+            trie.insert(word(), ());
+        }
+        for _ in 0..50 {
+            let query = word();
+            for metric in [Metric::Levenshtein, Metric::Damerau].iter() {
+                let found: Vec<_> =
+                    trie.fuzzy(&query, 2, *metric).into_iter().map(|(k, d, _)| (k, d)).collect();
+                let expected: Vec<_> = trie
+                    .iter()
+                    .map(|(k, _)| (distance(&k, &query, *metric), k))
+                    .filter(|(d, _)| *d <= 2)
+                    .map(|(d, k)| (k, d))
+                    .collect();
+                assert_eq!(found, expected);
+            }
+        }
     }
 }
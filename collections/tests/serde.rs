@@ -0,0 +1,118 @@
+//! Serde round trips of the collections
+//!
+//! They live outside of the unit tests, so the unit tests are built without serde_json
+//! and its `PartialEq<Value>` impls for primitives, which make untyped `vec![]` ambiguous
+
+#![cfg(feature = "serde_derive")]
+
+use collections::btree::{Map, PagedMap, PagedSet, Set};
+use collections::multimap::{deserialize_sorted, grouped, MultiMap, SortedMultiMap};
+use collections::slicemap::{BytesMap, SliceMap, StringMap};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Index {
+    #[serde(with = "grouped")]
+    topics: MultiMap<String, u32>,
+}
+
+#[derive(Deserialize)]
+struct Strict(#[serde(deserialize_with = "deserialize_sorted")] MultiMap<u32, u32>);
+
+#[test]
+fn sorts_flat_input() {
+    let json = r#"[{"key":2,"val":1},{"key":1,"val":2},{"key":2,"val":3}]"#;
+    let map: MultiMap<u32, u32> = serde_json::from_str(json).unwrap();
+    assert_eq!(map.find(&2).collect::<Vec<_>>(), vec![&1, &3]);
+    let out = serde_json::to_string(&map).unwrap();
+    assert_eq!(out, r#"[{"key":1,"val":2},{"key":2,"val":1},{"key":2,"val":3}]"#);
+}
+
+#[test]
+fn rejects_unsorted_strict_input() {
+    let sorted = r#"[{"key":1,"val":2},{"key":2,"val":1}]"#;
+    assert_eq!(serde_json::from_str::<Strict>(sorted).unwrap().0.len(), 2);
+    let unsorted = r#"[{"key":2,"val":1},{"key":1,"val":2}]"#;
+    let err = serde_json::from_str::<Strict>(unsorted).err().unwrap();
+    assert!(err.to_string().contains("record 1 is out of order"));
+}
+
+#[test]
+fn sorts_and_dedups_sorted_multimap() {
+    let records = r#"[{"key":2,"val":1},{"key":1,"val":5},{"key":1,"val":3}]"#;
+    let json = format!(r#"{{"records":{},"unique":false}}"#, records);
+    let map: SortedMultiMap<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert!(map.contains(&1, &3) && map.contains(&1, &5) && map.contains(&2, &1));
+    assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&1, &3), (&1, &5), (&2, &1)]);
+
+    let records = r#"[{"key":1,"val":2},{"key":0,"val":1},{"key":1,"val":2}]"#;
+    let json = format!(r#"{{"records":{},"unique":true}}"#, records);
+    let mut map: SortedMultiMap<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(map.len(), 2);
+    assert!(!map.insert(1, 2));
+    let json = json.replace("true", "false");
+    let map: SortedMultiMap<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(map.count(&1), 2);
+}
+
+#[test]
+fn map_of_lists() {
+    let json = r#"{"topics":{"b":[3,1],"a":[2],"c":[]}}"#;
+    let index: Index = serde_json::from_str(json).unwrap();
+    assert_eq!(index.topics.len(), 3);
+    assert_eq!(index.topics.find(&"b".to_owned()).collect::<Vec<_>>(), vec![&3, &1]);
+    let out = serde_json::to_string(&index).unwrap();
+    assert_eq!(out, r#"{"topics":{"a":[2],"b":[3,1]}}"#);
+}
+
+#[test]
+fn rejects_invalid_utf8() {
+    let map: BytesMap<u32> = vec![(&b"ok"[..], 1), (&b"\xff"[..], 2)].into();
+    let json = serde_json::to_string(&map).unwrap();
+    let err = serde_json::from_str::<StringMap<u32>>(&json).err().unwrap();
+    assert!(err.to_string().contains("slice map key 1 is not valid"));
+    let bytes: BytesMap<u32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(bytes.len(), 2);
+    let json = r#"{"buf":[97],"map":[[0,1],[2,2]]}"#;
+    let err = serde_json::from_str::<BytesMap<u32>>(json).err().unwrap();
+    assert!(err.to_string().contains("out of order"));
+}
+
+#[test]
+fn rejects_unsorted_and_duplicate_keys() {
+    let json = r#"{"buf":[98,97,97],"map":[[0,1],[1,2],[2,3]]}"#;
+    let err = serde_json::from_str::<StringMap<u32>>(json).err().unwrap();
+    assert!(err.to_string().contains("slice map key 1 is out of order"));
+    let json = r#"{"buf":[97,98,98],"map":[[0,1],[1,2],[2,3]]}"#;
+    let err = serde_json::from_str::<StringMap<u32>>(json).err().unwrap();
+    assert!(err.to_string().contains("slice map key 2 is a duplicate"));
+    let json = r#"{"buf":[97,98],"map":[[0,1],[1,2]]}"#;
+    let map = serde_json::from_str::<StringMap<u32>>(json).unwrap();
+    assert_eq!(map.binary_search("b"), Some(&2));
+}
+
+#[test]
+fn validates_slice_map_offsets() {
+    let json = r#"{"buf":[97,98],"map":[[3,1],[0,2]]}"#;
+    let err = serde_json::from_str::<SliceMap<u8, u32>>(json).err().unwrap();
+    assert!(err.to_string().contains("slice map key offsets are out of order"));
+    let json = r#"{"buf":[98,97],"map":[[0,1],[1,2]]}"#;
+    let mut map = serde_json::from_str::<SliceMap<u8, u32>>(json).unwrap();
+    assert!(!map.is_sorted());
+    map.sort();
+    assert_eq!(map.get_by_key(b"a"), Some(&2));
+}
+
+#[test]
+fn paged_layout_keeps_flat_format() {
+    let map: PagedMap<u32, u32> = (0..2_000).map(|k| (k, k * 2)).collect();
+    let json = serde_json::to_string(&map).unwrap();
+    assert!(json.starts_with(r#"[{"key":0,"val":0},{"key":1,"val":2},"#));
+    let flat: Map<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&flat).unwrap(), json);
+    let paged: PagedMap<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(paged, map);
+    let set: PagedSet<u32> = (0..3).collect();
+    let json = serde_json::to_string(&set).unwrap();
+    assert_eq!(serde_json::from_str::<Set<u32>>(&json).unwrap().into_vec(), [0, 1, 2]);
+}
@@ -1,103 +1,610 @@
+use collections::edit::{EditRows, Metric};
+use std::borrow::Borrow;
+use std::collections::hash_map::{HashMap, RandomState};
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FusedIterator;
+use std::ptr;
+use std::slice;
+
+/// A minimal acyclic automaton mapping sequences of `K` to values
+///
+/// Keys are inserted in ascending order, so only the states on the path of the last key
+/// can still change. When a key leaves that path, the states below the divergence are
+/// merged with equivalent registered states or registered themselves (Daciuk et al.).
+/// States are equivalent when they have the same value and the same transitions
 pub struct Dawg<K, V> {
-    nodes: Vec<Node<V>>,
-    edges: Vec<Edge<K>>,
+    nodes: Vec<Node<K, V>>,
+    /// Indices of the merged away nodes, reused by the next new states
+    free: Vec<usize>,
+    /// Minimized states by the hash of their contents
+    register: HashMap<u64, Vec<usize>>,
+    hasher: RandomState,
+    /// The last key and the states on its path, starting at the root
+    last: Vec<K>,
+    path: Vec<usize>,
+    len: usize,
+    finished: bool,
 }
 
-struct Node<V> {
+#[derive(PartialEq, Eq, Hash)]
+struct Node<K, V> {
     value: Option<V>,
+    /// Transitions sorted by key
+    edges: Vec<Edge<K>>,
 }
 
-impl<V> Node<V> {
-    pub fn new(value: Option<V>) -> Self {
-        Self { value }
-    }
-
-    pub fn empty() -> Self {
-        Self::new(None)
+impl<K, V> Node<K, V> {
+    fn new() -> Self {
+        Self { value: None, edges: Vec::new() }
     }
 }
 
+#[derive(PartialEq, Eq, Hash)]
 struct Edge<K> {
-    source: usize,
+    key: K,
     target: usize,
-    key: K
 }
 
+/// The reasons [Dawg::insert] rejects a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertError {
+    /// The key is less than the last inserted key
+    Unsorted,
+    /// The automaton was minimized by [Dawg::finish]
+    Finished,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsorted => write!(f, "keys must be inserted in ascending order"),
+            Self::Finished => write!(f, "keys can't be inserted after finish"),
+        }
+    }
+}
+
+impl Error for InsertError {}
+
 impl<K, V> Dawg<K, V> {
     pub fn new() -> Self {
-        let nodes = vec![Node::empty()];
-        let edges = vec![];
-        Self { nodes, edges }
+        Self {
+            nodes: vec![Node::new()],
+            free: Vec::new(),
+            register: HashMap::new(),
+            hasher: RandomState::new(),
+            last: Vec::new(),
+            path: vec![0],
+            len: 0,
+            finished: false,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of states including the root
+    #[inline]
+    pub fn num_states(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    #[inline]
+    pub fn root(&self) -> State<'_, K, V> {
+        State { dawg: self, idx: 0 }
+    }
+
+    /// Iterates over the entries in key order
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let root = self.node(0);
+        let stack = vec![root.edges.iter()];
+        Iter { dawg: self, stack, key: Vec::new(), first: root.value.as_ref(), len: self.len }
+    }
+
+    fn node(&self, idx: usize) -> &Node<K, V> {
+        &self.nodes[idx]
+    }
+
+    /// The transitions of the state in key order
+    #[inline]
+    fn children(&self, idx: usize) -> &[Edge<K>] {
+        &self.node(idx).edges
     }
+}
 
-    pub fn root(&self) -> &Node<V> {
-        &self.nodes[0]
+impl<K, V> Default for Dawg<K, V> {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn insert<I>(&mut self, keys: I, value: V) -> Option<V>
-    where I: Iterator<Item = K>
+impl<K: Ord, V> Dawg<K, V> {
+    pub fn find<I>(&self, keys: I) -> Option<&V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
     {
-        todo!()
+        let mut idx = 0;
+        for k in keys {
+            idx = self.child(idx, k.borrow())?;
+        }
+        self.node(idx).value.as_ref()
     }
 
-    pub fn remove<I>(&mut self, keys: I) -> Option<V>
-    where I: Iterator<Item = K>
+    #[inline]
+    pub fn contains<I>(&self, keys: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
     {
-        todo!()
+        self.find(keys).is_some()
     }
 
-    pub fn find<I>(&self, keys: I) -> Option<V>
-    where I: Iterator<Item = K>
+    /// Iterates over the states on the path of the keys starting at the root,
+    /// stops at the first key without a transition
+    #[inline]
+    pub fn traverse<I>(&self, keys: I) -> Traverse<'_, K, V, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
     {
-        todo!()
+        Traverse { inner: self, keys: keys.into_iter(), state: Some(0) }
     }
 
-    pub fn traverse<I>(&mut self, keys: I) -> Traverse<K, V, I>
-    where I: Iterator<Item = K>
+    /// Finds the keys within `max` edits of the query, in key order with their distances
+    ///
+    /// Walks the paths of the automaton with a row of the edit distance table per
+    /// state like `Trie::fuzzy` of the collections crate, with the same [EditRows].
+    /// Shared suffixes are walked once for every prefix which leads to them
+    pub fn fuzzy<Q>(&self, query: &[Q], max: usize, metric: Metric) -> Vec<(Vec<K>, usize, &V)>
+    where
+        Q: Borrow<K>,
+        K: Clone,
     {
-        todo!()
+        let rows = EditRows::new(query, max, metric);
+        let row = rows.first();
+        let mut search = Fuzzy { dawg: self, rows, key: Vec::new(), found: Vec::new() };
+        if let (Some(v), Some(d)) = (&self.node(0).value, search.rows.distance(&row)) {
+            search.found.push((Vec::new(), d, v));
+        }
+        search.visit(0, &[], &row);
+        search.found
     }
 
-    fn node(&self, idx: usize) -> &Node<V> {
-        &self.nodes[idx]
+    fn child(&self, idx: usize, k: &K) -> Option<usize> {
+        let edges = self.children(idx);
+        let i = edges.binary_search_by(|e| e.key.cmp(k)).ok()?;
+        Some(edges[i].target)
+    }
+}
+
+impl<K: Ord + Hash + Clone, V: Eq + Hash> Dawg<K, V> {
+    /// Inserts the value of a key not less than the last inserted key
+    ///
+    /// Returns the previous value if the key equals the last key
+    pub fn insert<I>(&mut self, keys: I, value: V) -> Result<Option<V>, InsertError>
+    where I: IntoIterator<Item = K>
+    {
+        if self.finished {
+            return Err(InsertError::Finished);
+        }
+        let keys: Vec<K> = keys.into_iter().collect();
+        if self.len > 0 && keys < self.last {
+            return Err(InsertError::Unsorted);
+        }
+        if self.len > 0 && keys == self.last {
+            let idx = self.path[keys.len()];
+            return Ok(self.nodes[idx].value.replace(value));
+        }
+        let common = self.last.iter().zip(&keys).take_while(|(a, b)| a == b).count();
+        self.minimize(common);
+        for k in &keys[common..] {
+            let idx = self.alloc();
+            let parent = self.path[self.path.len() - 1];
+            // the key is greater than the last one, so the new edge is the greatest
+            self.nodes[parent].edges.push(Edge { key: k.clone(), target: idx });
+            self.path.push(idx);
+        }
+        self.nodes[self.path[keys.len()]].value = Some(value);
+        self.last = keys;
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// Removes the key and returns its value
+    ///
+    /// States of a minimal automaton are shared by many keys, so the automaton is
+    /// rebuilt from the remaining entries in O(n). It stays finished if it was
+    pub fn remove<I>(&mut self, keys: I) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+        V: Clone,
+    {
+        let keys: Vec<K> = keys.into_iter().map(|k| k.borrow().clone()).collect();
+        let old = self.find(&keys)?.clone();
+        let mut dawg = Dawg::new();
+        for (k, v) in self.iter().filter(|(k, _)| *k != keys) {
+            // the entries come in key order
+            dawg.insert(k, v.clone()).unwrap();
+        }
+        if self.finished {
+            dawg.finish();
+        }
+        *self = dawg;
+        Some(old)
     }
 
-    fn children(&self, idx: usize, k: K) -> &[Edge<K>] {
-        todo!()
+    /// Minimizes the states on the path of the last key, after which no keys can be inserted
+    pub fn finish(&mut self) {
+        self.minimize(0);
+        self.finished = true;
     }
 
+    /// Replaces or registers the states on the path of the last key below `depth`
+    fn minimize(&mut self, depth: usize) {
+        while self.path.len() > depth + 1 {
+            let idx = self.path.pop().unwrap();
+            let parent = self.path[self.path.len() - 1];
+            let Self { nodes, register, hasher, .. } = self;
+            let bucket = register.entry(hasher.hash_one(&nodes[idx])).or_default();
+            match bucket.iter().copied().find(|i| nodes[*i] == nodes[idx]) {
+                Some(same) => {
+                    // the state was added last below its parent, so its edge is the last one
+                    nodes[parent].edges.last_mut().unwrap().target = same;
+                    nodes[idx] = Node::new();
+                    self.free.push(idx);
+                }
+                None => bucket.push(idx),
+            }
+        }
+    }
+
+    fn alloc(&mut self) -> usize {
+        match self.free.pop() {
+            Some(idx) => idx,
+            None => {
+                self.nodes.push(Node::new());
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
+
+/// A state of a [Dawg], equal states are shared by the keys leading to them
+pub struct State<'d, K, V> {
+    dawg: &'d Dawg<K, V>,
+    idx: usize,
+}
+
+impl<K, V> Clone for State<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for State<'_, K, V> {}
+
+impl<K, V> PartialEq for State<'_, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.dawg, other.dawg) && self.idx == other.idx
+    }
 }
 
+impl<K, V> Eq for State<'_, K, V> {}
+
+impl<'d, K, V> State<'d, K, V> {
+    /// The value of the key which ends in this state
+    #[inline]
+    pub fn value(&self) -> Option<&'d V> {
+        self.dawg.node(self.idx).value.as_ref()
+    }
+
+    #[inline]
+    pub fn is_final(&self) -> bool {
+        self.value().is_some()
+    }
+
+    /// Iterates over the transitions of the state in key order
+    pub fn children(&self) -> impl Iterator<Item = (&'d K, State<'d, K, V>)> {
+        let dawg = self.dawg;
+        dawg.children(self.idx).iter().map(move |e| (&e.key, State { dawg, idx: e.target }))
+    }
+
+    pub fn child(&self, k: &K) -> Option<Self>
+    where K: Ord
+    {
+        let idx = self.dawg.child(self.idx, k)?;
+        Some(State { dawg: self.dawg, idx })
+    }
+}
+
+impl<K, V> fmt::Debug for State<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("State").field(&self.idx).finish()
+    }
+}
+
+/// An iterator over the states on the path of a key, see [Dawg::traverse]
 pub struct Traverse<'d, K, V, I> {
     inner: &'d Dawg<K, V>,
-    keys: I
+    keys: I,
+    /// The state to yield next
+    state: Option<usize>,
+}
+
+impl<'d, K: Ord, V, I> Iterator for Traverse<'d, K, V, I>
+where
+    I: Iterator,
+    I::Item: Borrow<K>,
+{
+    type Item = State<'d, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.state.take()?;
+        self.state = match self.keys.next() {
+            Some(k) => self.inner.child(idx, k.borrow()),
+            None => None,
+        };
+        Some(State { dawg: self.inner, idx })
+    }
+}
+
+impl<'d, K: Ord, V, I> FusedIterator for Traverse<'d, K, V, I>
+where
+    I: Iterator,
+    I::Item: Borrow<K>,
+{
+}
+
+/// An iterator over the entries of a [Dawg] in key order
+pub struct Iter<'d, K, V> {
+    dawg: &'d Dawg<K, V>,
+    /// Transitions left to visit at every level
+    stack: Vec<slice::Iter<'d, Edge<K>>>,
+    key: Vec<K>,
+    /// The value of the root, which comes first
+    first: Option<&'d V>,
+    len: usize,
+}
+
+impl<'d, K: Clone, V> Iterator for Iter<'d, K, V> {
+    type Item = (Vec<K>, &'d V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(v) = self.first.take() {
+            self.len -= 1;
+            return Some((Vec::new(), v));
+        }
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(edge) => {
+                    let node = self.dawg.node(edge.target);
+                    self.key.push(edge.key.clone());
+                    self.stack.push(node.edges.iter());
+                    if let Some(v) = &node.value {
+                        self.len -= 1;
+                        return Some((self.key.clone(), v));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.key.pop();
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K: Clone, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K: Clone, V> FusedIterator for Iter<'_, K, V> {}
+
+/// The state of a fuzzy search below the root
+struct Fuzzy<'d, 'q, K, V, Q> {
+    dawg: &'d Dawg<K, V>,
+    rows: EditRows<'q, Q>,
+    /// The key of the visited state
+    key: Vec<K>,
+    found: Vec<(Vec<K>, usize, &'d V)>,
+}
+
+impl<K: Ord + Clone, V, Q: Borrow<K>> Fuzzy<'_, '_, K, V, Q> {
+    /// Visits the transitions of the state, `row` holds the distances of its key
+    /// to the prefixes of the query and `prev` those of the key without its last element
+    fn visit(&mut self, idx: usize, prev: &[usize], row: &[usize]) {
+        let dawg = self.dawg;
+        for edge in dawg.children(idx) {
+            let eq = |a: &K, b: &Q| a == b.borrow();
+            let next = match self.rows.next(prev, row, self.key.last(), &edge.key, eq) {
+                Some(next) => next,
+                None => continue,
+            };
+            self.key.push(edge.key.clone());
+            if let (Some(v), Some(d)) = (&dawg.node(edge.target).value, self.rows.distance(&next)) {
+                self.found.push((self.key.clone(), d, v));
+            }
+            self.visit(edge.target, row, &next);
+            self.key.pop();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use random::Source;
+    use std::collections::BTreeMap;
 
-    const TEXTS: Vec<&'static str> = vec![
-
+    const TEXTS: &[&str] = &[
+        "city", "cities", "pity", "pities", "tap", "taps", "top", "tops", "ten", "tens",
     ];
 
     #[test]
     fn insert_unique_words() {
-        assert!(false)
+        let mut words = TEXTS.to_vec();
+        words.sort_unstable();
+        let mut dawg = Dawg::new();
+        for w in &words {
+            assert_eq!(dawg.insert(w.bytes(), ()), Ok(None));
+        }
+        dawg.finish();
+        assert_eq!(dawg.len(), TEXTS.len());
+        assert!(words.iter().all(|w| dawg.contains(w.bytes())));
+        assert!(!dawg.contains("cit".bytes()));
+        assert!(!dawg.contains("topss".bytes()));
+        assert!(!dawg.contains("".bytes()));
     }
 
     #[test]
     fn store_unique_pair() {
-        assert!(false)
+        let mut dawg = Dawg::new();
+        assert_eq!(dawg.insert("key".chars(), 1), Ok(None));
+        assert_eq!(dawg.insert("key".chars(), 2), Ok(Some(1)));
+        assert_eq!(dawg.find("key".chars()), Some(&2));
+        assert_eq!(dawg.find("ke".chars()), None);
+        assert_eq!(dawg.insert("kex".chars(), 3), Err(InsertError::Unsorted));
+        dawg.finish();
+        assert_eq!(dawg.insert("kez".chars(), 3), Err(InsertError::Finished));
+        assert_eq!(dawg.len(), 1);
+        assert_eq!(dawg.num_states(), 4);
     }
 
     #[test]
     fn static_insert_and_find() {
-        assert!(false)
+        let mut dawg = Dawg::new();
+        for w in ["tap", "taps", "top", "tops"].iter() {
+            dawg.insert(w.chars(), ()).unwrap();
+        }
+        dawg.finish();
+        // the root, "t", "ta" merged with "to", "tap" merged with "top" and "taps" with "tops"
+        assert_eq!(dawg.num_states(), 5);
+
+        let mut dawg = Dawg::new();
+        let mut words = TEXTS.to_vec();
+        words.sort_unstable();
+        for w in &words {
+            dawg.insert(w.chars(), w.len() % 2).unwrap();
+        }
+        dawg.finish();
+        for w in TEXTS {
+            assert_eq!(dawg.find(w.chars()), Some(&(w.len() % 2)));
+        }
+        // suffixes with equal values share states
+        assert!(dawg.num_states() < words.iter().map(|w| w.len()).sum::<usize>());
+    }
+
+    /// A key of less than `max_len` letters taken from the bits of `r`
+    fn random_key(r: u64, max_len: u64) -> Vec<u8> {
+        (0..r % max_len).map(|i| b"abcd"[(r >> (8 + i * 2)) as usize % 4]).collect()
     }
 
     #[test]
     fn random_insert_and_find() {
-        assert!(false)
+        let mut source = random::default(42);
+        let mut next = || source.read_u64();
+        let mut oracle = BTreeMap::new();
+        for _ in 0..3000 {
+            let r = next();
+            let key = random_key(r, 9);
+            oracle.insert(key, r % 4);
+        }
+        let mut dawg = Dawg::new();
+        let mut prev: Option<&Vec<u8>> = None;
+        for (k, v) in &oracle {
+            if let Some(prev) = prev.filter(|_| next() % 16 == 0) {
+                assert_eq!(dawg.insert(prev.clone(), *v), Ok(Some(oracle[prev])));
+                assert_eq!(dawg.insert(prev.clone(), oracle[prev]), Ok(Some(*v)));
+            }
+            assert_eq!(dawg.insert(k.clone(), *v), Ok(None));
+            if let Some(prev) = prev {
+                assert_eq!(dawg.insert(prev.clone(), *v), Err(InsertError::Unsorted));
+            }
+            prev = Some(k);
+        }
+        dawg.finish();
+        assert_eq!(dawg.len(), oracle.len());
+        for _ in 0..3000 {
+            let r = next();
+            let key = random_key(r, 10);
+            assert_eq!(dawg.find(&key), oracle.get(&key));
+        }
+        assert!(oracle.iter().all(|(k, v)| dawg.find(k) == Some(v)));
+    }
+
+    #[test]
+    fn fuzzy_lookup() {
+        let mut words = TEXTS.to_vec();
+        words.sort_unstable();
+        let mut dawg = Dawg::new();
+        for w in &words {
+            dawg.insert(w.bytes(), *w).unwrap();
+        }
+        dawg.finish();
+        let find = |query: &str, max, metric| -> Vec<(&str, usize)> {
+            let found = dawg.fuzzy(query.as_bytes(), max, metric);
+            found.into_iter().map(|(_, d, w)| (*w, d)).collect()
+        };
+        assert_eq!(find("tip", 1, Metric::Levenshtein), vec![("tap", 1), ("top", 1)]);
+        assert_eq!(find("tpo", 1, Metric::Levenshtein), Vec::<(&str, usize)>::new());
+        assert_eq!(find("tpo", 1, Metric::Damerau), vec![("top", 1)]);
+        let found = find("cites", 2, Metric::Levenshtein);
+        assert_eq!(found, vec![("cities", 1), ("city", 2), ("pities", 2)]);
+        let exact = dawg.fuzzy(b"pity", 0, Metric::Damerau);
+        assert_eq!(exact, vec![(b"pity".to_vec(), 0, &"pity")]);
+    }
+
+    #[test]
+    fn states_and_traversal() {
+        let mut dawg = Dawg::new();
+        for w in ["tap", "taps", "top", "tops"].iter() {
+            dawg.insert(w.chars(), ()).unwrap();
+        }
+        dawg.finish();
+        let t = dawg.root().child(&'t').unwrap();
+        let children: Vec<_> = t.children().map(|(k, _)| *k).collect();
+        assert_eq!(children, vec!['a', 'o']);
+        assert_eq!(t.child(&'a'), t.child(&'o'));
+        assert_ne!(t.child(&'a'), Some(t));
+        let path: Vec<_> = dawg.traverse("tapsx".chars()).map(|s| s.is_final()).collect();
+        assert_eq!(path, vec![false, false, false, true, true]);
+        assert_eq!(dawg.traverse("x".chars()).count(), 1);
+        assert_eq!(dawg.find("tapsx".chars()), None);
+        let keys: Vec<String> = dawg.iter().map(|(k, _)| k.into_iter().collect()).collect();
+        assert_eq!(keys, vec!["tap", "taps", "top", "tops"]);
+    }
+
+    #[test]
+    fn remove_rebuilds_minimal_automaton() {
+        let mut dawg = Dawg::new();
+        for w in ["tap", "taps", "tip", "top", "tops"].iter() {
+            dawg.insert(w.chars(), 1).unwrap();
+        }
+        assert_eq!(dawg.remove("tip".chars()), Some(1));
+        assert_eq!(dawg.remove("tip".chars()), None);
+        assert_eq!(dawg.len(), 4);
+        assert_eq!(dawg.insert("tops".chars(), 2), Ok(Some(1)));
+        assert_eq!(dawg.insert("tr".chars(), 3), Ok(None));
+        dawg.finish();
+        assert_eq!(dawg.remove("tr".chars()), Some(3));
+        assert_eq!(dawg.insert("u".chars(), 1), Err(InsertError::Finished));
+        assert!(!dawg.contains("tip".chars()) && dawg.contains("taps".chars()));
+        let values: Vec<_> = dawg.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![1, 1, 1, 2]);
+        // "tops" has another value now, so the "tap" and "top" branches stay apart
+        assert_eq!(dawg.num_states(), 8);
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,7 @@
 mod pool;
 mod bitpool;
-mod custom;
\ No newline at end of file
+
+mod custom;
+pub use custom::Dawg;
+pub use custom::InsertError;
+pub use collections::edit::Metric;
@@ -1,14 +1,50 @@
 use alloc::sync::Arc;
-use core::{ops::{Deref, DerefMut}, sync::atomic::{AtomicUsize, Ordering}};
+use core::hint;
 use core::marker::PhantomData;
 use core::mem;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
+/// A thread-safe reference counted pointer
 pub struct Rc<T: ?Sized>(Arc<T>);
 
 impl<T> Rc<T> {
     pub fn new(data: T) -> Self {
         Self(Arc::new(data))
     }
+
+    fn into_raw(this: Self) -> *const T {
+        Arc::into_raw(this.0)
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must come from [Rc::into_raw] and own one reference
+    unsafe fn from_raw(p: *const T) -> Self {
+        Self(Arc::from_raw(p))
+    }
+}
+
+impl<T: ?Sized> Rc<T> {
+    /// Returns a mutable reference if there are no other pointers to the value
+    #[inline]
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        Arc::get_mut(&mut this.0)
+    }
+
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T: Clone> Rc<T> {
+    /// Returns a mutable reference, cloning the value first if it is shared
+    #[inline]
+    pub fn make_mut(this: &mut Self) -> &mut T {
+        Arc::make_mut(&mut this.0)
+    }
 }
 
 impl<T: ?Sized> Clone for Rc<T> {
@@ -17,32 +53,54 @@ impl<T: ?Sized> Clone for Rc<T> {
     }
 }
 
-impl<T> Deref for Rc<T> {
+impl<T: ?Sized> Deref for Rc<T> {
     type Target = T;
-    
+
+    #[inline]
     fn deref(&self) -> &Self::Target {
-        todo!()
+        &self.0
     }
 }
 
-impl<T> DerefMut for Rc<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        todo!()
-    }
+/// An atomically replaceable [Rc]
+///
+/// Readers never block: [RcCell::get] announces the reader in one of two counters,
+/// loads the pointer and takes a reference. Writers are serialized by a spin lock and
+/// publish the new value with an atomic swap. A reader may have loaded the old pointer
+/// without taking its reference yet, so a writer waits for a grace period before it
+/// drops the old value.
+///
+/// Readers pick the counter by the parity of an epoch. A writer advances the epoch,
+/// which sends new readers to the other counter, and waits until the counter of the
+/// previous epoch drains. It does so twice to cover the readers of both parities.
+/// Only the readers in flight at the swap are waited for, and each of them takes just
+/// a reference, so replaced values are dropped by the write however busy the readers are
+pub struct RcCell<T> {
+    ptr: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    /// In-flight readers by the parity of the epoch they started in
+    readers: [AtomicUsize; 2],
+    writer: AtomicBool,
+    marker: PhantomData<Rc<T>>,
 }
 
-pub struct RcCell<T>(AtomicUsize, PhantomData<Rc<T>>);
+unsafe impl<T: Send + Sync> Send for RcCell<T> {}
+unsafe impl<T: Send + Sync> Sync for RcCell<T> {}
 
 impl<T> Drop for RcCell<T> {
     fn drop(&mut self) {
-        self.take();
+        let p = *self.ptr.get_mut();
+        drop(unsafe { Rc::from_raw(p) });
     }
 }
 
 impl<T> From<Rc<T>> for RcCell<T> {
     fn from(src: Rc<T>) -> Self {
-        let rc = unsafe { mem::transmute(src) };
-        Self(AtomicUsize::new(rc), PhantomData)
+        let ptr = AtomicPtr::new(Rc::into_raw(src) as *mut T);
+        let epoch = AtomicUsize::new(0);
+        let readers = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        let writer = AtomicBool::new(false);
+        Self { ptr, epoch, readers, writer, marker: PhantomData }
     }
 }
 
@@ -58,30 +116,124 @@ impl<T> RcCell<T> {
         Self::from(rc)
     }
 
+    /// Returns the current value without blocking
     pub fn get(&self) -> Rc<T> {
-        let t = self.take();
-        let out = t.clone();
-        self.put(t);
-        out
+        let readers = &self.readers[self.epoch.load(Ordering::SeqCst) & 1];
+        readers.fetch_add(1, Ordering::SeqCst);
+        let p = self.ptr.load(Ordering::SeqCst);
+        // a writer which replaced `p` after the load waits for this reader
+        let rc = unsafe {
+            Arc::increment_strong_count(p);
+            Rc::from_raw(p)
+        };
+        readers.fetch_sub(1, Ordering::SeqCst);
+        rc
     }
 
+    /// Replaces the value and returns the old one
     pub fn set(&self, t: Rc<T>) -> Rc<T> {
-        let old = self.take();
-        self.put(t);
-        old
+        self.update(|rc| mem::replace(rc, t))
     }
 
-    fn take(&self) -> Rc<T> {
-        loop {
-            match self.0.swap(0, Ordering::Acquire) {
-                0 => {}
-                n => return unsafe { mem::transmute(n) }
+    /// Updates the value under the writer lock, which makes the read-modify-write atomic
+    ///
+    /// The closure gets the current value, the value it leaves is published if it changed.
+    /// Use [Rc::make_mut] for copy-on-write updates
+    pub fn update<R, F: FnOnce(&mut Rc<T>) -> R>(&self, f: F) -> R {
+        let _lock = WriterLock::new(&self.writer);
+        let p = self.ptr.load(Ordering::SeqCst);
+        let mut rc = unsafe {
+            Arc::increment_strong_count(p);
+            Rc::from_raw(p)
+        };
+        let out = f(&mut rc);
+        if !ptr::eq(Arc::as_ptr(&rc.0), p) {
+            let old = self.ptr.swap(Rc::into_raw(rc) as *mut T, Ordering::SeqCst);
+            self.synchronize();
+            drop(unsafe { Rc::from_raw(old) });
+        }
+        out
+    }
+
+    /// Waits until every reader which started before the call has taken its reference
+    fn synchronize(&self) {
+        for _ in 0..2 {
+            let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+            // new readers announce themselves in the other counter, so this one drains
+            let readers = &self.readers[epoch & 1];
+            while readers.load(Ordering::SeqCst) != 0 {
+                hint::spin_loop();
             }
         }
     }
+}
 
-    fn put(&self, t: Rc<T>) {
-        debug_assert_eq!(self.0.load(Ordering::SeqCst), 0);
-        self.0.store(unsafe { mem::transmute(t) }, Ordering::Release);
+/// Holds the writer spin lock until dropped, so a panicking update releases it
+struct WriterLock<'l>(&'l AtomicBool);
+
+impl<'l> WriterLock<'l> {
+    fn new(flag: &'l AtomicBool) -> Self {
+        let lock = || flag.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed);
+        while lock().is_err() {
+            hint::spin_loop();
+        }
+        Self(flag)
+    }
+}
+
+impl Drop for WriterLock<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Counts the live values
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Counted {
+        fn new(live: &Arc<AtomicUsize>) -> Self {
+            live.fetch_add(1, Ordering::SeqCst);
+            Self(live.clone())
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn drops_replaced_values_while_readers_run() {
+        const READERS: usize = 4;
+        let live = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcCell::new(Counted::new(&live)));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let (cell, done) = (cell.clone(), done.clone());
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        drop(cell.get());
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..10_000 {
+            drop(cell.set(Rc::new(Counted::new(&live))));
+            // the current value and at most one value held by every reader
+            assert!(live.load(Ordering::SeqCst) <= READERS + 1);
+        }
+        done.store(true, Ordering::Relaxed);
+        readers.into_iter().for_each(|r| r.join().unwrap());
+        drop(cell);
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+}